@@ -0,0 +1,46 @@
+#![no_main]
+
+use std::fmt::Write;
+
+use axum::extract::Query;
+use futures_util::FutureExt;
+use libfuzzer_sys::fuzz_target;
+
+// The handlers live in the binary crate, so the module is compiled in directly
+#[allow(dead_code)]
+#[path = "../../src/day_2.rs"]
+mod day_2;
+
+/// Percent-encodes every byte, so any input makes a valid query string.
+fn query_uri(path: &str, params: [(&str, &[u8]); 2]) -> axum::http::Uri {
+    let mut uri = format!("{path}?");
+    for (idx, (name, value)) in params.iter().enumerate() {
+        if idx > 0 {
+            uri.push('&');
+        }
+        uri.push_str(name);
+        uri.push('=');
+        for byte in value.iter() {
+            write!(uri, "%{byte:02X}").unwrap();
+        }
+    }
+    uri.parse().unwrap()
+}
+
+// Whatever the query holds, the extractor either rejects it or the handler answers with an
+// address; neither may panic
+fuzz_target!(|input: (&[u8], &[u8])| {
+    let (a, b) = input;
+
+    let uri = query_uri("/2/v6/dest", [("from", a), ("key", b)]);
+    if let Ok(params) = Query::<day_2::V6DestQParams>::try_from_uri(&uri) {
+        let dest = day_2::v6_dest(params).now_or_never().unwrap();
+        assert!(dest.parse::<std::net::Ipv6Addr>().is_ok());
+    }
+
+    let uri = query_uri("/2/v6/key", [("from", a), ("to", b)]);
+    if let Ok(params) = Query::<day_2::V6KeyQParams>::try_from_uri(&uri) {
+        let key = day_2::v6_key(params).now_or_never().unwrap();
+        assert!(key.parse::<std::net::Ipv6Addr>().is_ok());
+    }
+});
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS quotes_fts;
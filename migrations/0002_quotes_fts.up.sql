@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS quotes_fts ON quotes USING GIN (to_tsvector('english', quote));
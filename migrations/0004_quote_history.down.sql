@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS quotes_history ON quotes;
+DROP FUNCTION IF EXISTS record_quote_history();
+DROP TABLE IF EXISTS quote_history;
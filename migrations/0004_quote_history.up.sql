@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS quote_history (
+    quote_id UUID NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    version INT NOT NULL,
+    author TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (quote_id, version)
+);
+
+INSERT INTO quote_history (quote_id, version, author, quote, changed_at)
+SELECT id, version, author, quote, created_at
+FROM quotes
+ON CONFLICT DO NOTHING;
+
+CREATE OR REPLACE FUNCTION record_quote_history() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO quote_history (quote_id, version, author, quote)
+    VALUES (NEW.id, NEW.version, NEW.author, NEW.quote)
+    ON CONFLICT DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER quotes_history
+AFTER INSERT OR UPDATE OF author, quote, version ON quotes
+FOR EACH ROW EXECUTE FUNCTION record_quote_history();
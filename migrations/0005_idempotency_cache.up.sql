@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS idempotency_cache (
+    key TEXT PRIMARY KEY,
+    quote_id UUID NOT NULL REFERENCES quotes (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,3 @@
+ALTER TABLE quotes
+    DROP CONSTRAINT IF EXISTS quotes_author_length,
+    DROP CONSTRAINT IF EXISTS quotes_quote_length;
@@ -0,0 +1,3 @@
+ALTER TABLE quotes
+    ADD CONSTRAINT quotes_author_length CHECK (char_length(author) BETWEEN 1 AND 255),
+    ADD CONSTRAINT quotes_quote_length CHECK (char_length(quote) BETWEEN 1 AND 4096);
@@ -1,13 +1,70 @@
-use axum::{body::Body, http::StatusCode, response::Response};
+use std::sync::Arc;
 
+use axum::{
+    body::Body,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+const DEFAULT_SEEK_URL: &str = "https://www.youtube.com/watch?v=9Gc4QTqslN4";
+
+/// Where `/-1/seek` redirects to
+#[derive(Debug, Clone)]
+pub struct SeekUrl(Arc<str>);
+
+impl SeekUrl {
+    /// Reads `SEEK_URL`, falling back to the video when it is unset
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("SEEK_URL").ok().as_deref())
+    }
+
+    fn new(url: Option<&str>) -> Self {
+        Self(url.unwrap_or(DEFAULT_SEEK_URL).into())
+    }
+}
+
+#[utoipa::path(get, path = "/", responses((status = 200, body = String)))]
 pub async fn hello_world() -> &'static str {
     "Hello, bird!"
 }
 
-pub async fn seek() -> Response {
+#[utoipa::path(get, path = "/-1/seek", responses((status = 302, description = "Redirect to the video"), (status = 500, description = "`SEEK_URL` is set but empty or not a valid header")))]
+pub async fn seek(State(SeekUrl(url)): State<SeekUrl>) -> Response {
+    if url.is_empty() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
     Response::builder()
         .status(StatusCode::FOUND)
-        .header("Location", "https://www.youtube.com/watch?v=9Gc4QTqslN4")
+        .header("Location", &*url)
         .body(Body::empty())
-        .unwrap()
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[tokio::test]
+async fn test_seek_url() {
+    let location = |res: Response| res.headers().get("Location").cloned();
+
+    let res = seek(State(SeekUrl::new(None))).await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(location(res).unwrap(), DEFAULT_SEEK_URL);
+
+    let res = seek(State(SeekUrl::new(Some("https://example.com/elves")))).await;
+    assert_eq!(location(res).unwrap(), "https://example.com/elves");
+
+    for url in ["", "https://example.com/\n"] {
+        let res = seek(State(SeekUrl::new(Some(url)))).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(location(res), None);
+    }
+}
+
+#[test]
+fn test_seek_url_from_env() {
+    // Nothing else reads `SEEK_URL`, and both cases share one test so they can't race
+    std::env::set_var("SEEK_URL", "https://example.com/reindeer");
+    assert_eq!(&*SeekUrl::from_env().0, "https://example.com/reindeer");
+
+    std::env::remove_var("SEEK_URL");
+    assert_eq!(&*SeekUrl::from_env().0, DEFAULT_SEEK_URL);
 }
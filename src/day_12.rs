@@ -1,7 +1,7 @@
-use core::simd;
 use std::{
-    ops::AddAssign,
-    simd::{num::SimdInt, Simd},
+    fmt,
+    ops::RangeInclusive,
+    simd::Simd,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc, Mutex,
@@ -9,11 +9,14 @@ use std::{
 };
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i8)]
@@ -23,8 +26,46 @@ enum Tile {
     Milk = 1,
 }
 
-// row-major board
-struct Board([Tile; 16]);
+impl Tile {
+    /// `0` (empty), `1` (cookie) or `2` (milk), as in the state, JSON and `Debug` output
+    fn code(self) -> u8 {
+        match self {
+            Tile::Empty => 0,
+            Tile::Cookie => 1,
+            Tile::Milk => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Tile::Empty),
+            1 => Some(Tile::Cookie),
+            2 => Some(Tile::Milk),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PushError {
+    ColumnFull,
+    InvalidColumn,
+}
+
+/// Side lengths a board can have; 4 unless `/12/reset` asks otherwise
+const BOARD_SIZES: RangeInclusive<usize> = 4..=6;
+/// Enough lanes for a row of the largest board
+const LANES: usize = 8;
+/// 36 tiles take 57 bits in base 3, which leaves the top ones of the state for the size
+const SIZE_SHIFT: u32 = 58;
+
+/// Row-major square board, in JSON rows of `0` (empty), `1` (cookie) or `2` (milk) tiles
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "Vec<Vec<u8>>", into = "Vec<Vec<u8>>")]
+struct Board {
+    size: usize,
+    tiles: Vec<Tile>,
+}
 
 const WHITE_SQUARE: &str = "⬜";
 const COOKIE_EMOJI: &str = "🍪";
@@ -32,242 +73,972 @@ const BLACK_SQUARE: &str = "⬛";
 const MILK_GLASS: &str = "🥛";
 
 impl Board {
+    pub fn empty(size: usize) -> Self {
+        debug_assert!(BOARD_SIZES.contains(&size));
+        Board {
+            size,
+            tiles: vec![Tile::Empty; size * size],
+        }
+    }
+
+    /// Tiles are packed as base-3 digits, the first one lowest, under the size above
+    /// `SIZE_SHIFT`. That is stored as the offset from 4, so an empty 4x4 board is still `0`.
     pub fn decode(state: u64) -> Self {
-        let mut tiles = [Tile::Empty; 16];
-        for i in 0..16 {
-            let val = ((state >> (2 * i)) & 0b11) as u8;
-            tiles[i] = match val {
-                0 => Tile::Empty,
-                1 => Tile::Cookie,
-                2 => Tile::Milk,
-                _ => unreachable!(),
-            };
+        let size = (state >> SIZE_SHIFT) as usize + BOARD_SIZES.start();
+        let mut digits = state & ((1 << SIZE_SHIFT) - 1);
+        let mut board = Board::empty(size);
+        for tile in board.tiles.iter_mut() {
+            *tile = Tile::from_code((digits % 3) as u8).unwrap();
+            digits /= 3;
         }
-        Board(tiles)
+        board
     }
 
     pub fn encode(&self) -> u64 {
-        let mut state = 0_u64;
-        for (i, &tile) in self.0.iter().enumerate() {
-            let tile = match tile {
-                Tile::Empty => 0,
-                Tile::Cookie => 1,
-                Tile::Milk => 2,
-            };
-            state |= (tile as u64) << (2 * i);
-        }
-        state
+        let digits = self
+            .tiles
+            .iter()
+            .rev()
+            .fold(0, |state, tile| state * 3 + u64::from(tile.code()));
+        ((self.size - BOARD_SIZES.start()) as u64) << SIZE_SHIFT | digits
+    }
+
+    /// Builds a board from rows of `0` (empty), `1` (cookie) and `2` (milk).
+    #[cfg(test)]
+    pub fn from_rows<const N: usize>(rows: [[u8; N]; N]) -> Option<Self> {
+        Self::try_from(rows.map(Vec::from).to_vec()).ok()
     }
 
     pub fn new_random(rng: &mut rand::rngs::StdRng) -> Self {
-        let mut board = [Tile::Empty; 16];
-        for item in board.iter_mut() {
+        let mut board = Board::empty(4);
+        for item in board.tiles.iter_mut() {
             *item = match rng.gen::<bool>() {
                 true => Tile::Cookie,
                 false => Tile::Milk,
             }
         }
-        Self(board)
+        board
     }
 
-    pub fn render(&self) -> String {
-        let mut s = String::new();
-
-        for row in 0..4 {
-            s.push_str(WHITE_SQUARE);
-            for col in 0..4 {
-                let tile = self.0[row * 4 + col];
-                let ch = match tile {
-                    Tile::Empty => BLACK_SQUARE,
-                    Tile::Cookie => COOKIE_EMOJI,
-                    Tile::Milk => MILK_GLASS,
-                };
-                s.push_str(ch);
-            }
-            s.push_str(WHITE_SQUARE);
-            s.push('\n');
-        }
-
-        s.push_str(&WHITE_SQUARE.repeat(6));
-
-        match self.check_for_winner() {
-            Ok(Some(winner)) => {
-                s.push('\n');
-                s.push_str(match winner {
-                    Tile::Cookie => "🍪 wins!",
-                    Tile::Milk => "🥛 wins!",
-                    _ => unreachable!(),
-                });
-            }
-            Err(_) => {
-                s.push('\n');
-                s.push_str("No winner.");
-            }
-            _ => {}
+    /// Tiles in a row needed to win: four, or five on the 6x6 board
+    fn win_length(&self) -> usize {
+        if self.size == 6 {
+            5
+        } else {
+            4
         }
-        s.push('\n');
-
-        s
     }
 
+    /// `Ok(Some(_))` for a complete line, `Err(())` for a full board without one, otherwise
+    /// `Ok(None)`. Lines are checked first, so filling the last cell can still win.
     fn check_for_winner(&self) -> Result<Option<Tile>, ()> {
-        fn check_value(val: i8) -> Option<Tile> {
-            match val {
-                4 => Some(Tile::Milk),
-                -4 => Some(Tile::Cookie),
-                _ => None,
-            }
-        }
-
-        // We'll keep track of row sums and column sums using SIMD vectors.
-        // Initialize everything to zero.
-        let mut winner_cols = Simd::from_array([0i8; 4]);
-        let mut winner_rows = [0i8; 4];
+        let (size, win) = (self.size, self.win_length());
+        let check_value = |val: i8| match val {
+            val if val == win as i8 => Some(Tile::Milk),
+            val if val == -(win as i8) => Some(Tile::Cookie),
+            _ => None,
+        };
+        let tile = |row: usize, col: usize| self.tiles[row * size + col] as i8;
 
-        let mut winner_d_top_to_bot = 0i8;
-        let mut winner_d_bot_to_top = 0i8;
-
-        // Iterate through rows and zip them with the winner_rows iterator
-        for ((row, line), winner_row) in (0..4)
-            .zip(self.0.chunks_exact(4))
-            .zip(winner_rows.iter_mut())
-        {
-            let row_line =
-                Simd::from_array([line[0] as i8, line[1] as i8, line[2] as i8, line[3] as i8]);
+        // Each row padded out to a SIMD vector, so that summing `win` consecutive rows gives
+        // every vertical line through them at once
+        let rows: Vec<Simd<i8, LANES>> = self
+            .tiles
+            .chunks_exact(size)
+            .map(|line| {
+                let mut lanes = [0i8; LANES];
+                for (lane, &tile) in lanes.iter_mut().zip(line) {
+                    *lane = tile as i8;
+                }
+                Simd::from_array(lanes)
+            })
+            .collect();
+        let winner_cols = rows
+            .windows(win)
+            .flat_map(|window| window.iter().copied().sum::<Simd<i8, LANES>>().to_array());
 
-            // Update column sums using SIMD addition
-            winner_cols += row_line;
+        let winner_rows = rows.iter().flat_map(|row| {
+            let row = row.to_array();
+            (0..=size - win)
+                .map(|start| row[start..start + win].iter().sum::<i8>())
+                .collect::<Vec<_>>()
+        });
 
-            // Update the corresponding row sum using a SIMD reduction
-            *winner_row += row_line.reduce_sum();
+        let starts = (0..=size - win).flat_map(|row| (0..=size - win).map(move |col| (row, col)));
+        let winner_diagonals = starts.flat_map(|(row, col)| {
+            let top_to_bot = (0..win).map(|i| tile(row + i, col + i)).sum::<i8>();
+            let bot_to_top = (0..win)
+                .map(|i| tile(row + i, col + win - 1 - i))
+                .sum::<i8>();
+            [top_to_bot, bot_to_top]
+        });
 
-            // Update diagonals
-            winner_d_top_to_bot.add_assign(line[row] as i8);
-            winner_d_bot_to_top.add_assign(line[3 - row] as i8);
-        }
-        let winner_cols_arr = winner_cols.to_array();
-
-        // Combine all values into a single iterator
-        let diagonal_check = [winner_d_top_to_bot, winner_d_bot_to_top];
-        let all_results = winner_cols_arr
-            .iter()
-            .chain(winner_rows.iter())
-            .chain(diagonal_check.iter());
-
-        // Check each value
-        for &val in all_results {
-            if let Some(tile) = check_value(val) {
-                return Ok(Some(tile));
-            }
+        if let Some(tile) = winner_cols
+            .chain(winner_rows)
+            .chain(winner_diagonals)
+            .find_map(check_value)
+        {
+            return Ok(Some(tile));
         }
 
-        // Check for draw: if no empty slots are left, it's a tie
-        if !self.0.contains(&Tile::Empty) {
+        // Check for draw: if no empty slots are left and nobody has a line, it's a tie
+        if !self.tiles.contains(&Tile::Empty) {
             Err(())
         } else {
             Ok(None)
         }
     }
 
-    fn get_col(&self, col: usize) -> [Tile; 4] {
-        let line = [
-            self.0[col],
-            self.0[col + 4],
-            self.0[col + 8],
-            self.0[col + 12],
-        ];
-        line
+    fn get_col(&self, col: usize) -> Vec<Tile> {
+        self.tiles
+            .iter()
+            .skip(col)
+            .step_by(self.size)
+            .copied()
+            .collect()
     }
 
-    fn push_item(&mut self, col_idx: usize, item: Tile) -> Result<(), ()> {
-        let col = self.get_col(col_idx);
-        let (idx_last_empty, _) = col
+    fn push_item(&mut self, col_idx: usize, item: Tile) -> Result<(), PushError> {
+        if col_idx >= self.size {
+            return Err(PushError::InvalidColumn);
+        }
+        let (idx_last_empty, _) = self
+            .get_col(col_idx)
             .iter()
             .enumerate()
             .rev()
             .find(|(_idx, x)| **x == Tile::Empty)
-            .ok_or(())?;
-        self.0[col_idx + (idx_last_empty * 4)] = item;
+            .ok_or(PushError::ColumnFull)?;
+        self.tiles[col_idx + (idx_last_empty * self.size)] = item;
+        Ok(())
+    }
+}
+
+impl TryFrom<Vec<Vec<u8>>> for Board {
+    type Error = &'static str;
+
+    fn try_from(rows: Vec<Vec<u8>>) -> Result<Self, Self::Error> {
+        let size = rows.len();
+        if !BOARD_SIZES.contains(&size) || rows.iter().any(|row| row.len() != size) {
+            return Err("tiles must be a 4x4, 5x5 or 6x6 grid");
+        }
+        let tiles = rows
+            .into_iter()
+            .flatten()
+            .map(Tile::from_code)
+            .collect::<Option<_>>()
+            .ok_or("tiles must be 0, 1 or 2")?;
+        Ok(Board { size, tiles })
+    }
+}
+
+impl From<Board> for Vec<Vec<u8>> {
+    fn from(board: Board) -> Self {
+        board
+            .tiles
+            .chunks_exact(board.size)
+            .map(|row| row.iter().map(|tile| tile.code()).collect())
+            .collect()
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.tiles.chunks_exact(self.size) {
+            f.write_str(WHITE_SQUARE)?;
+            for tile in row {
+                f.write_str(match tile {
+                    Tile::Empty => BLACK_SQUARE,
+                    Tile::Cookie => COOKIE_EMOJI,
+                    Tile::Milk => MILK_GLASS,
+                })?;
+            }
+            f.write_str(WHITE_SQUARE)?;
+            f.write_str("\n")?;
+        }
+        for _ in 0..self.size + 2 {
+            f.write_str(WHITE_SQUARE)?;
+        }
+
+        match self.check_for_winner() {
+            Ok(Some(Tile::Cookie)) => f.write_str("\n🍪 wins!")?,
+            Ok(Some(Tile::Milk)) => f.write_str("\n🥛 wins!")?,
+            Ok(Some(Tile::Empty)) => unreachable!(),
+            Err(_) => f.write_str("\nNo winner.")?,
+            Ok(None) => {}
+        }
+        f.write_str("\n")
+    }
+}
+
+/// Tiles as they are encoded: `0` empty, `1` cookie and `2` milk, one row per line.
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let separator = if i % self.size == self.size - 1 {
+                '\n'
+            } else {
+                ' '
+            };
+            write!(f, "{}{separator}", tile.code())?;
+        }
         Ok(())
     }
 }
 
+/// Only written while `BOARD_HISTORY` is locked, so checks made under that lock stay valid.
 static BOARD: AtomicU64 = AtomicU64::new(0);
 
-pub async fn board() -> Response {
-    let s = render_board();
+const MAX_HISTORY: usize = 100;
 
-    (StatusCode::OK, s).into_response()
+/// `BOARD` after each successful `place` since the last `reset`, oldest first.
+static BOARD_HISTORY: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+#[utoipa::path(get, path = "/12/board", responses((status = 200, description = "Rendered board", body = String), (status = 304, description = "Board unchanged since `If-None-Match`")))]
+pub async fn board(headers: HeaderMap) -> Response {
+    // A single load backs both the tag and the body, so they can't disagree with each other
+    let state = BOARD.load(Ordering::Relaxed);
+    let etag = format!("\"{state:x}\"");
+
+    if if_none_match(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    (
+        [(header::ETAG, etag)],
+        board_response(StatusCode::OK, Board::decode(state).to_string()),
+    )
+        .into_response()
 }
 
+/// Spells out the charset and the length in bytes, which some clients need to decode the emoji.
+fn board_response(status: StatusCode, s: String) -> Response {
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_owned()),
+            (header::CONTENT_LENGTH, s.len().to_string()),
+        ],
+        s,
+    )
+        .into_response()
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
 fn render_board() -> String {
     let board = BOARD.load(Ordering::Relaxed);
     let board = Board::decode(board);
-    let s = board.render();
-    s
+    board.to_string()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ResetBody {
+    /// Four, five or six rows of as many tiles: `0` empty, `1` cookie, `2` milk
+    #[schema(value_type = Vec<Vec<u8>>)]
+    tiles: Board,
+}
+
+#[derive(Default, Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResetQuery {
+    /// Side length from 4 to 6, defaulting to 4 or to the size of the tiles sent
+    size: Option<usize>,
 }
 
-pub async fn reset(State(rng): State<Arc<Mutex<StdRng>>>) -> Response {
+#[utoipa::path(
+    post,
+    path = "/12/reset",
+    params(ResetQuery),
+    request_body(content = Option<ResetBody>, description = "Starting tiles; an empty body clears the board"),
+    responses(
+        (status = 200, description = "Rendered board", body = String),
+        (status = 400, description = "`size` out of range, or tiles that aren't a square grid of that size of 0, 1 or 2"),
+    )
+)]
+pub async fn reset(
+    State(rng): State<Arc<Mutex<StdRng>>>,
+    Query(query): Query<ResetQuery>,
+    body: Bytes,
+) -> Response {
+    if query.size.is_some_and(|size| !BOARD_SIZES.contains(&size)) {
+        return (StatusCode::BAD_REQUEST, "Size must be between 4 and 6\n").into_response();
+    }
+    let state = if body.iter().all(u8::is_ascii_whitespace) {
+        Board::empty(query.size.unwrap_or(4)).encode()
+    } else {
+        let Some(body) = serde_json::from_slice::<ResetBody>(&body)
+            .ok()
+            .filter(|body| query.size.is_none_or(|size| size == body.tiles.size))
+        else {
+            return (StatusCode::BAD_REQUEST,).into_response();
+        };
+        body.tiles.encode()
+    };
+
     let mut rng = rng.lock().unwrap();
     *rng = rand::rngs::StdRng::seed_from_u64(2024);
     drop(rng);
 
-    let _board = BOARD
-        .fetch_update(Ordering::Release, Ordering::Acquire, |_old_state| Some(0))
-        .unwrap();
+    let mut history = BOARD_HISTORY.lock().unwrap();
+    history.clear();
+    BOARD.store(state, Ordering::Release);
+    drop(history);
 
-    let s = render_board();
+    // Rendered from `state` rather than reloaded, as a `place` may land as soon as the lock drops
+    let s = Board::decode(state).to_string();
 
     (StatusCode::OK, s).into_response()
 }
 
-pub async fn random_board(State(rng): State<Arc<Mutex<StdRng>>>) -> Response {
-    let mut rng = rng.lock().unwrap();
-    let board = Board::new_random(&mut rng);
-    drop(rng);
-    BOARD.store(board.encode(), Ordering::Relaxed);
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RandomBoardQuery {
+    /// Seeds a one-off RNG instead of drawing from the shared one
+    seed: Option<u64>,
+}
 
-    let s = render_board();
+#[utoipa::path(get, path = "/12/random-board", params(RandomBoardQuery), responses((status = 200, description = "Rendered board", body = String)))]
+pub async fn random_board(
+    State(rng): State<Arc<Mutex<StdRng>>>,
+    Query(query): Query<RandomBoardQuery>,
+) -> Response {
+    let board = match query.seed {
+        Some(seed) => Board::new_random(&mut StdRng::seed_from_u64(seed)),
+        None => {
+            let mut rng = rng.lock().unwrap();
+            Board::new_random(&mut rng)
+        }
+    };
+    // Swapped in like a `reset`, so the history of the old board goes with it
+    let mut history = BOARD_HISTORY.lock().unwrap();
+    history.clear();
+    BOARD.store(board.encode(), Ordering::Release);
+    drop(history);
 
-    (StatusCode::OK, s).into_response()
+    (StatusCode::OK, board.to_string()).into_response()
 }
 
+#[utoipa::path(
+    post,
+    path = "/12/place/{team}/{column}",
+    params(
+        ("team" = String, Path, description = "`cookie` or `milk`"),
+        ("column" = u8, Path, description = "Column from 1 to the board size"),
+    ),
+    responses(
+        (status = 200, description = "Rendered board", body = String),
+        (status = 400, description = "Column out of range"),
+        (status = 409, description = "Column full"),
+        (status = 422, description = "Unknown team"),
+        (status = 503, description = "Game over"),
+    )
+)]
 pub async fn place(Path((team, column)): Path<(String, String)>) -> Response {
     let team = match team.as_str() {
         "cookie" => Tile::Cookie,
         "milk" => Tile::Milk,
-        _ => return (StatusCode::BAD_REQUEST,).into_response(),
+        _ => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Invalid team: {team}\n"),
+            )
+                .into_response()
+        }
     };
 
-    let column = match column.parse::<usize>() {
-        Ok(c) if (1..=4).contains(&c) => c - 1,
-        _ => return (StatusCode::BAD_REQUEST,).into_response(),
-    };
+    let column = column.parse::<usize>().ok();
+
+    // Held from the checks through the update, so no other move or reset can change the board in
+    // between, and history entries land in the order the moves did
+    let mut history = BOARD_HISTORY.lock().unwrap();
+    let mut board = Board::decode(BOARD.load(Ordering::Acquire));
 
-    let board_val = BOARD.load(Ordering::Relaxed);
-    let board = Board::decode(board_val);
+    let Some(column) = column.filter(|column| (1..=board.size).contains(column)) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Column must be between 1 and {}\n", board.size),
+        )
+            .into_response();
+    };
 
     // Early check if game over
     let state = board.check_for_winner();
     if state.is_err() || state.ok().flatten().is_some() {
-        return (StatusCode::SERVICE_UNAVAILABLE, render_board()).into_response();
-    }
-
-    let res = BOARD.fetch_update(Ordering::Release, Ordering::Acquire, |old| {
-        let mut b = Board::decode(old);
-        b.push_item(column, team).ok().map(|_| b.encode())
-    });
-
-    let s = render_board();
-    match res {
-        Ok(new_val) => {
-            let new_board = Board::decode(new_val);
-            match new_board.check_for_winner() {
-                Ok(Some(_)) => (StatusCode::OK, s).into_response(),
-                Ok(None) => (StatusCode::OK, s).into_response(),
-                Err(_) => (StatusCode::SERVICE_UNAVAILABLE, s).into_response(),
+        return board_response(StatusCode::SERVICE_UNAVAILABLE, board.to_string());
+    }
+
+    match board.push_item(column - 1, team) {
+        Ok(()) => {
+            let state = board.encode();
+            BOARD.store(state, Ordering::Release);
+            if history.len() == MAX_HISTORY {
+                history.remove(0);
+            }
+            history.push(state);
+            drop(history);
+            let s = board.to_string();
+            match board.check_for_winner() {
+                Ok(Some(_)) => board_response(StatusCode::OK, s),
+                Ok(None) => board_response(StatusCode::OK, s),
+                Err(_) => board_response(StatusCode::SERVICE_UNAVAILABLE, s),
             }
         }
+        Err(PushError::ColumnFull) => (StatusCode::CONFLICT, "Column full\n").into_response(),
         Err(_) => (StatusCode::SERVICE_UNAVAILABLE,).into_response(),
     }
 }
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Index of the first entry to return
+    from: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/12/history",
+    params(HistoryQuery),
+    responses((status = 200, description = "Rendered boards after each move since the last reset, oldest first, at most 100", body = Vec<String>))
+)]
+pub async fn history(Query(query): Query<HistoryQuery>) -> Json<Vec<String>> {
+    let history = BOARD_HISTORY.lock().unwrap();
+    let from = query.from.unwrap_or(0).min(history.len());
+
+    Json(
+        history[from..]
+            .iter()
+            .map(|&state| Board::decode(state).to_string())
+            .collect(),
+    )
+}
+
+/// Serialises tests that read or write the global `BOARD`.
+#[cfg(test)]
+static BOARD_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[cfg(test)]
+fn test_rng() -> State<Arc<Mutex<StdRng>>> {
+    State(Arc::new(Mutex::new(StdRng::seed_from_u64(2024))))
+}
+
+#[cfg(test)]
+async fn body_string(res: Response) -> String {
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_board_etag() {
+    let _lock = BOARD_LOCK.lock().await;
+    BOARD.store(0, Ordering::Relaxed);
+
+    let res = board(HeaderMap::new()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res.headers()[header::ETAG].clone();
+    assert_eq!(etag, "\"0\"");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::IF_NONE_MATCH, etag);
+    let res = board(headers.clone()).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(body.is_empty());
+
+    let res = place(Path(("cookie".to_string(), "1".to_string()))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = board(headers).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_ne!(res.headers()[header::ETAG], "\"0\"");
+}
+
+#[tokio::test]
+async fn test_reset_with_tiles() {
+    let _lock = BOARD_LOCK.lock().await;
+    let body = r#"{"tiles":[[0,1,2,0],[0,0,0,0],[0,0,0,0],[1,2,1,2]]}"#;
+
+    let res = reset(test_rng(), Default::default(), Bytes::from(body)).await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        body_string(res).await,
+        "⬜⬛🍪🥛⬛⬜\n\
+         ⬜⬛⬛⬛⬛⬜\n\
+         ⬜⬛⬛⬛⬛⬜\n\
+         ⬜🍪🥛🍪🥛⬜\n\
+         ⬜⬜⬜⬜⬜⬜\n"
+    );
+    assert_eq!(
+        body_string(board(HeaderMap::new()).await)
+            .await
+            .lines()
+            .nth(3),
+        Some("⬜🍪🥛🍪🥛⬜")
+    );
+
+    let res = reset(test_rng(), Default::default(), Bytes::new()).await;
+    assert_eq!(BOARD.load(Ordering::Relaxed), 0);
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_reset_rejects_bad_tiles() {
+    for body in [
+        r#"{"tiles":[[0,1,3,0],[0,0,0,0],[0,0,0,0],[0,0,0,0]]}"#,
+        r#"{"tiles":[[0,1,2],[0,0,0,0],[0,0,0,0],[0,0,0,0]]}"#,
+        r#"{"tiles":[[0,0,0,0],[0,0,0,0],[0,0,0,0]]}"#,
+        "not json",
+    ] {
+        let res = reset(test_rng(), Default::default(), Bytes::from(body)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{body}");
+    }
+}
+
+#[tokio::test]
+async fn test_random_board_seed() {
+    let _lock = BOARD_LOCK.lock().await;
+    let seeded = |seed| Query(RandomBoardQuery { seed: Some(seed) });
+
+    let first = body_string(random_board(test_rng(), seeded(7)).await).await;
+    let again = body_string(random_board(test_rng(), seeded(7)).await).await;
+    let other = body_string(random_board(test_rng(), seeded(8)).await).await;
+
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+}
+
+#[tokio::test]
+async fn test_random_board_seed_leaves_shared_rng() {
+    let _lock = BOARD_LOCK.lock().await;
+    let rng = test_rng();
+    let unseeded = || Query(RandomBoardQuery { seed: None });
+
+    random_board(
+        State(rng.0.clone()),
+        Query(RandomBoardQuery { seed: Some(1) }),
+    )
+    .await;
+    let after_seeded = body_string(random_board(State(rng.0.clone()), unseeded()).await).await;
+    let fresh = body_string(random_board(test_rng(), unseeded()).await).await;
+
+    assert_eq!(after_seeded, fresh);
+}
+
+#[tokio::test]
+async fn test_win_on_last_empty_cell() {
+    let _lock = BOARD_LOCK.lock().await;
+    // Only the top-left cell is free, and filling it with a cookie completes the top row
+    let tiles = r#"{"tiles":[[0,1,1,1],[2,2,1,2],[1,1,2,1],[2,2,1,2]]}"#;
+    reset(test_rng(), Default::default(), Bytes::from(tiles)).await;
+
+    let res = place(Path(("cookie".to_string(), "1".to_string()))).await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = body_string(res).await;
+    assert!(body.ends_with("🍪 wins!\n"), "{body}");
+    assert!(!body.contains("No winner."));
+}
+
+#[tokio::test]
+async fn test_draw_on_last_empty_cell() {
+    let _lock = BOARD_LOCK.lock().await;
+    let tiles = r#"{"tiles":[[0,1,2,1],[2,2,1,2],[1,1,2,1],[2,2,1,2]]}"#;
+    reset(test_rng(), Default::default(), Bytes::from(tiles)).await;
+
+    let res = place(Path(("cookie".to_string(), "1".to_string()))).await;
+
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body_string(res).await.ends_with("No winner.\n"));
+}
+
+#[tokio::test]
+async fn test_place_after_draw() {
+    let _lock = BOARD_LOCK.lock().await;
+    let drawn = r#"{"tiles":[[1,1,2,1],[2,2,1,2],[1,1,2,1],[2,2,1,2]]}"#;
+    reset(test_rng(), Default::default(), Bytes::from(drawn)).await;
+    let before = render_board();
+
+    for team in ["cookie", "milk"] {
+        for column in 1..=4 {
+            let res = place(Path((team.to_owned(), column.to_string()))).await;
+            assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+            let body = body_string(res).await;
+            assert_eq!(body, before);
+            assert!(body.ends_with("No winner.\n"), "{body}");
+            assert!(!body.contains("wins!"));
+        }
+    }
+    assert_eq!(render_board(), before);
+}
+
+#[tokio::test]
+async fn test_history() {
+    let _lock = BOARD_LOCK.lock().await;
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+    let all = || Query(HistoryQuery { from: None });
+
+    for (team, column) in [
+        ("milk", "1"),
+        ("cookie", "2"),
+        ("milk", "3"),
+        ("cookie", "4"),
+        ("milk", "1"),
+    ] {
+        let res = place(Path((team.to_string(), column.to_string()))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let Json(boards) = history(all()).await;
+    assert_eq!(boards.len(), 5);
+    assert_eq!(boards[4], render_board());
+    assert_eq!(boards[0].lines().nth(3), Some("⬜🥛⬛⬛⬛⬜"));
+    let Json(tail) = history(Query(HistoryQuery { from: Some(3) })).await;
+    assert_eq!(tail, boards[3..]);
+    let Json(past_end) = history(Query(HistoryQuery { from: Some(10) })).await;
+    assert!(past_end.is_empty());
+
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+    assert!(history(all()).await.0.is_empty());
+}
+
+#[test]
+fn test_anti_diagonal_win() {
+    for team in [Tile::Cookie, Tile::Milk] {
+        // `col + win - 1 - i` walks from the top-right corner to the bottom-left one
+        let mut board = Board::empty(4);
+        for row in 0..4 {
+            board.tiles[row * 4 + (3 - row)] = team;
+        }
+
+        assert_eq!(board.check_for_winner(), Ok(Some(team)));
+        // The same cells read back column by column, as `push_item` sees them
+        for col in 0..4 {
+            assert_eq!(board.get_col(col)[3 - col], team);
+        }
+    }
+}
+
+#[test]
+fn test_full_board_without_line_is_draw() {
+    let board = Board::from_rows([[1, 1, 2, 1], [2, 2, 1, 2], [1, 1, 2, 1], [2, 2, 1, 2]]).unwrap();
+
+    assert_eq!(board.check_for_winner(), Err(()));
+}
+
+#[test]
+fn test_full_board_with_line_is_win() {
+    // Win detection runs before the draw check, so a line on a full board still wins
+    let board = Board::from_rows([[1, 1, 1, 1], [2, 2, 1, 2], [1, 1, 2, 1], [2, 2, 1, 2]]).unwrap();
+
+    assert_eq!(board.check_for_winner(), Ok(Some(Tile::Cookie)));
+}
+
+#[tokio::test]
+async fn test_random_board_clears_history() {
+    let _lock = BOARD_LOCK.lock().await;
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+    let res = place(Path(("milk".to_owned(), "1".to_owned()))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let random =
+        body_string(random_board(test_rng(), Query(RandomBoardQuery { seed: None })).await).await;
+
+    assert_eq!(random, render_board());
+    assert!(history(Query(HistoryQuery { from: None }))
+        .await
+        .0
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_board_content_headers() {
+    let _lock = BOARD_LOCK.lock().await;
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+
+    for res in [
+        board(HeaderMap::new()).await,
+        place(Path(("cookie".to_owned(), "1".to_owned()))).await,
+    ] {
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        let length = res.headers()[header::CONTENT_LENGTH].clone();
+        let body = body_string(res).await;
+        assert_eq!(length, body.len().to_string());
+        assert!(body.len() > body.chars().count());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_reset_races_place() {
+    let _lock = BOARD_LOCK.lock().await;
+    // The board and history as seen under the lock right after a reset, and once the racing move
+    // has finished too
+    let snapshot = || {
+        let history = BOARD_HISTORY.lock().unwrap();
+        (
+            Board::decode(BOARD.load(Ordering::Acquire)),
+            history.clone(),
+        )
+    };
+    let check = |(board, history): (Board, Vec<u64>)| {
+        let placed = board
+            .tiles
+            .iter()
+            .filter(|&&tile| tile != Tile::Empty)
+            .count();
+        match placed {
+            0 => assert!(history.is_empty()),
+            1 => assert_eq!(history, [board.encode()]),
+            _ => panic!("a move from before the reset survived it:\n{board}"),
+        }
+    };
+
+    for i in 0..1000 {
+        reset(test_rng(), Default::default(), Bytes::new()).await;
+        let column = (i % 4 + 1).to_string();
+        let place = tokio::spawn(place(Path(("cookie".to_owned(), column))));
+        let reset = tokio::spawn(async move {
+            reset(test_rng(), Default::default(), Bytes::new()).await;
+            snapshot()
+        });
+
+        check(reset.await.unwrap());
+        assert_eq!(place.await.unwrap().status(), StatusCode::OK);
+        check(snapshot());
+    }
+}
+
+#[tokio::test]
+async fn test_place_rejections() {
+    let _lock = BOARD_LOCK.lock().await;
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+    let place_at = |team: &str, column: &str| place(Path((team.to_owned(), column.to_owned())));
+
+    let res = place_at("water", "1").await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body_string(res).await, "Invalid team: water\n");
+
+    for column in ["0", "5", "abc"] {
+        let res = place_at("cookie", column).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_string(res).await, "Column must be between 1 and 4\n");
+    }
+}
+
+#[test]
+fn test_push_item_errors() {
+    let mut board = Board::decode(0);
+    for team in [Tile::Cookie, Tile::Milk, Tile::Cookie, Tile::Milk] {
+        assert_eq!(board.push_item(0, team), Ok(()));
+    }
+    assert_eq!(board.push_item(0, Tile::Cookie), Err(PushError::ColumnFull));
+    assert_eq!(
+        board.push_item(4, Tile::Cookie),
+        Err(PushError::InvalidColumn)
+    );
+}
+
+#[tokio::test]
+async fn test_place_full_column_and_won_board() {
+    let _lock = BOARD_LOCK.lock().await;
+    let place_at = |team: &str| place(Path((team.to_owned(), "1".to_owned())));
+
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+    for team in ["cookie", "milk", "cookie", "milk"] {
+        assert_eq!(place_at(team).await.status(), StatusCode::OK);
+    }
+    let res = place_at("cookie").await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert_eq!(body_string(res).await, "Column full\n");
+
+    let won = r#"{"tiles":[[0,0,0,0],[0,0,0,0],[0,0,0,0],[1,1,1,1]]}"#;
+    reset(test_rng(), Default::default(), Bytes::from(won)).await;
+    let res = place_at("milk").await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body_string(res).await.ends_with("🍪 wins!\n"));
+}
+
+#[test]
+fn test_board_display_and_debug() {
+    let board = Board::from_rows([[0, 1, 2, 0], [0; 4], [0; 4], [1, 2, 1, 2]]).unwrap();
+    assert_eq!(
+        format!("{board}"),
+        "⬜⬛🍪🥛⬛⬜\n\
+         ⬜⬛⬛⬛⬛⬜\n\
+         ⬜⬛⬛⬛⬛⬜\n\
+         ⬜🍪🥛🍪🥛⬜\n\
+         ⬜⬜⬜⬜⬜⬜\n"
+    );
+    assert_eq!(format!("{board:?}"), "0 1 2 0\n0 0 0 0\n0 0 0 0\n1 2 1 2\n");
+
+    let won = Board::from_rows([[2, 0, 0, 0], [2, 1, 0, 0], [2, 1, 0, 0], [2, 1, 0, 0]]).unwrap();
+    assert!(format!("{won}").ends_with("⬜⬜⬜⬜⬜⬜\n🥛 wins!\n"));
+    let drawn = Board::from_rows([[1, 1, 2, 1], [2, 2, 1, 2], [1, 1, 2, 1], [2, 2, 1, 2]]).unwrap();
+    assert!(format!("{drawn}").ends_with("⬜⬜⬜⬜⬜⬜\nNo winner.\n"));
+}
+
+#[test]
+fn test_board_json() {
+    let board = Board::from_rows([[0, 1, 2, 0], [0; 4], [0; 4], [1, 2, 1, 2]]).unwrap();
+    let json = "[[0,1,2,0],[0,0,0,0],[0,0,0,0],[1,2,1,2]]";
+
+    assert_eq!(serde_json::to_string(&board).unwrap(), json);
+    assert_eq!(serde_json::from_str::<Board>(json).unwrap(), board);
+    for json in ["[[0,1,3,0],[0,0,0,0],[0,0,0,0],[0,0,0,0]]", "[[0,0,0,0]]"] {
+        assert!(serde_json::from_str::<Board>(json).is_err(), "{json}");
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #![proptest_config(proptest::prelude::ProptestConfig::with_cases(1000))]
+
+    #[test]
+    fn test_board_json_round_trip(
+        rows in proptest::array::uniform4(proptest::array::uniform4(0u8..3))
+    ) {
+        let board = Board::from_rows(rows).unwrap();
+        let json = serde_json::to_string(&board).unwrap();
+        let parsed: Board = serde_json::from_str(&json).unwrap();
+        proptest::prop_assert_eq!(parsed.encode(), board.encode());
+        proptest::prop_assert_eq!(Vec::<Vec<u8>>::from(parsed), rows.map(Vec::from).to_vec());
+    }
+}
+
+#[cfg(test)]
+fn sized(size: usize) -> Query<ResetQuery> {
+    Query(ResetQuery { size: Some(size) })
+}
+
+#[tokio::test]
+async fn test_reset_sizes() {
+    let _lock = BOARD_LOCK.lock().await;
+
+    for size in BOARD_SIZES {
+        let res = reset(test_rng(), sized(size), Bytes::new()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let board = body_string(res).await;
+        let lines: Vec<_> = board.lines().collect();
+        assert_eq!(lines.len(), size + 1, "{board}");
+        assert_eq!(lines[size], WHITE_SQUARE.repeat(size + 2));
+        assert_eq!(
+            Board::decode(BOARD.load(Ordering::Relaxed)),
+            Board::empty(size)
+        );
+    }
+    // The default size keeps the empty board at state `0`
+    reset(test_rng(), Default::default(), Bytes::new()).await;
+    assert_eq!(BOARD.load(Ordering::Relaxed), 0);
+
+    for size in [0, 3, 7] {
+        let res = reset(test_rng(), sized(size), Bytes::new()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+    let tiles = r#"{"tiles":[[0,0,0,0,0],[0,0,0,0,0],[0,0,0,0,0],[0,0,0,0,0],[0,0,0,0,0]]}"#;
+    let res = reset(test_rng(), Default::default(), Bytes::from(tiles)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(Board::decode(BOARD.load(Ordering::Relaxed)).size, 5);
+    let res = reset(test_rng(), sized(6), Bytes::from(tiles)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_place_on_larger_boards() {
+    let _lock = BOARD_LOCK.lock().await;
+    let place_at = |column: &str| place(Path(("milk".to_owned(), column.to_owned())));
+
+    // Four in a column on 4x4 and 5x5, but five on 6x6
+    for (size, win) in [(4, 4), (5, 4), (6, 5)] {
+        reset(test_rng(), sized(size), Bytes::new()).await;
+        let column = size.to_string();
+        for _ in 1..win {
+            let res = place_at(&column).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(!body_string(res).await.contains("wins!"));
+        }
+        let res = place_at(&column).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(
+            body_string(res).await.ends_with("🥛 wins!\n"),
+            "{size}x{size}"
+        );
+
+        reset(test_rng(), sized(size), Bytes::new()).await;
+        let res = place_at(&(size + 1).to_string()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(res).await,
+            format!("Column must be between 1 and {size}\n")
+        );
+    }
+}
+
+#[test]
+fn test_larger_board_lines() {
+    // Rows, diagonals and anti-diagonals that don't start in a corner
+    let row = Board::from_rows([
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 1],
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+    ]);
+    let diagonal = Board::from_rows([
+        [0, 0, 0, 0, 0, 0],
+        [0, 2, 0, 0, 0, 0],
+        [0, 0, 2, 0, 0, 0],
+        [0, 0, 0, 2, 0, 0],
+        [0, 0, 0, 0, 2, 0],
+        [0, 0, 0, 0, 0, 2],
+    ]);
+    let anti_diagonal = Board::from_rows([
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 1],
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 0, 0],
+    ]);
+    let four_on_six = Board::from_rows([
+        [0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0],
+        [2, 2, 2, 2, 0, 1],
+    ]);
+
+    assert_eq!(row.unwrap().check_for_winner(), Ok(Some(Tile::Cookie)));
+    assert_eq!(diagonal.unwrap().check_for_winner(), Ok(Some(Tile::Milk)));
+    assert_eq!(
+        anti_diagonal.unwrap().check_for_winner(),
+        Ok(Some(Tile::Cookie))
+    );
+    assert_eq!(four_on_six.unwrap().check_for_winner(), Ok(None));
+}
+
+#[test]
+fn test_largest_board_fits_the_state() {
+    let mut full = Board::empty(6);
+    full.tiles.fill(Tile::Milk);
+    let state = full.encode();
+
+    assert!(state < 1 << 60);
+    assert_eq!(Board::decode(state), full);
+    assert_eq!(Board::decode(state).size, 6);
+}
@@ -1,21 +1,120 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroU64,
+    sync::{LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use axum::{
     body::{Body, Bytes},
+    extract::Query,
     http::{
         header::{COOKIE, SET_COOKIE},
-        HeaderMap, StatusCode,
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
     },
     response::{IntoResponse, Response},
     Json,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonschema::JSONSchema;
 use jsonwebtoken::{
     decode as jwt_decode, decode_header, encode, errors::ErrorKind, Algorithm, DecodingKey,
     EncodingKey, Header, Validation,
 };
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
 
 const SECRET: &[u8; 9] = b"my-secret";
 
-pub async fn wrap(Json(claims): Json<serde_json::Value>) -> Response {
-    let key = b"secret";
+/// Claims carried in the `gift` cookie: the registered ones this service looks at, plus
+/// whatever else the client sent.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct GiftClaims {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Compiled `$schema`s, keyed by their JSON text so a repeated schema compiles once
+static SCHEMAS: LazyLock<Mutex<HashMap<String, JSONSchema>>> = LazyLock::new(Default::default);
+/// Clients pick the schemas, so the cache starts over rather than growing without bound
+const MAX_CACHED_SCHEMAS: usize = 64;
+
+/// Strips an inline `"$schema"` from the claims and checks the rest against it.
+///
+/// Schema URLs are refused rather than fetched, so clients can't make the server issue requests.
+fn check_claims(claims: &mut GiftClaims) -> Result<(), AppError> {
+    let Some(schema) = claims.extra.remove("$schema") else {
+        return Ok(());
+    };
+    if !schema.is_object() {
+        return Err(AppError::BadRequest(
+            "`$schema` must be an inline JSON Schema object".to_owned(),
+        ));
+    }
+
+    let key = schema.to_string();
+    let mut schemas = SCHEMAS.lock().unwrap();
+    if !schemas.contains_key(&key) {
+        let compiled = JSONSchema::compile(&schema)
+            .map_err(|e| AppError::BadRequest(format!("Invalid `$schema`: {e}")))?;
+        if schemas.len() >= MAX_CACHED_SCHEMAS {
+            schemas.clear();
+        }
+        schemas.insert(key.clone(), compiled);
+    }
+
+    let claims = serde_json::to_value(&*claims).unwrap();
+    schemas[&key].validate(&claims).map_err(|violations| {
+        AppError::Unprocessable(
+            violations
+                .map(|violation| match violation.instance_path.to_string() {
+                    path if path.is_empty() => violation.to_string(),
+                    path => format!("{path}: {violation}"),
+                })
+                .collect(),
+        )
+    })
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WrapQuery {
+    /// Seconds until the token expires; an `exp` in the claims wins over this
+    #[param(value_type = Option<u64>, minimum = 1)]
+    ttl: Option<NonZeroU64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/16/wrap",
+    params(WrapQuery),
+    request_body(content = Object, description = "Claims, optionally with an inline `$schema` they must satisfy"),
+    responses(
+        (status = 200, description = "Claims wrapped into the `gift` cookie"),
+        (status = 400, description = "`ttl` is not a positive integer, or `$schema` is not a valid inline schema"),
+        (status = 422, description = "The body is not a claims object, or the claims break `$schema`")
+    )
+)]
+pub async fn wrap(Query(query): Query<WrapQuery>, Json(mut claims): Json<GiftClaims>) -> Response {
+    if let Some(ttl) = query.ttl {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        claims.exp.get_or_insert(now.saturating_add(ttl.get()));
+    }
+
+    if let Err(e) = check_claims(&mut claims) {
+        return e.into_response();
+    }
+
     // Set-Cookie header: gift=(JWT)
     let jwt = encode(
         &Header::default(),
@@ -31,47 +130,339 @@ pub async fn wrap(Json(claims): Json<serde_json::Value>) -> Response {
         .unwrap()
 }
 
-pub async fn unwrap(headers: HeaderMap) -> Response {
-    // decode the Cookie: gift=(JWT)
-    // if not there, respond with 400
+/// The JWT in a `Cookie: gift=...` header.
+fn gift_cookie(headers: &HeaderMap) -> Option<String> {
     let Some(cookie_header) = headers.get(COOKIE) else {
-        dbg!("content type header not present");
-        return (StatusCode::BAD_REQUEST).into_response();
+        tracing::debug!("cookie header not present");
+        return None;
     };
-
     let token = String::from_utf8_lossy(cookie_header.as_bytes());
-    let Some(token) = token.strip_prefix("gift=") else {
+    token.strip_prefix("gift=").map(str::to_owned)
+}
+
+#[utoipa::path(get, path = "/16/unwrap", responses((status = 200, description = "Claims from the `gift` cookie", body = Object), (status = 400, description = "Missing or invalid cookie")))]
+pub async fn unwrap(headers: HeaderMap) -> Response {
+    // decode the Cookie: gift=(JWT)
+    // if not there, respond with 400
+    let Some(token) = gift_cookie(&headers) else {
         return (StatusCode::BAD_REQUEST).into_response();
     };
     let mut validation = Validation::new(Algorithm::HS256);
     validation.required_spec_claims = Default::default();
     validation.validate_exp = false;
 
-    let token =
-        jwt_decode::<serde_json::Value>(&token, &DecodingKey::from_secret(SECRET), &validation);
-    dbg!(&token);
-    let Ok(token) = token else {
-        dbg!("invalid token");
+    let token = jwt_decode::<GiftClaims>(&token, &DecodingKey::from_secret(SECRET), &validation);
+    let token = match token {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::debug!(error = %e, "invalid token");
+            return (StatusCode::BAD_REQUEST).into_response();
+        }
+    };
+
+    Json(token.claims).into_response()
+}
+
+static X_SIGNATURE_NOT_VERIFIED: HeaderName = HeaderName::from_static("x-signature-not-verified");
+
+#[utoipa::path(
+    get,
+    path = "/16/cookie-payload",
+    responses(
+        (status = 200, description = "Claims from the `gift` cookie, signature unchecked", body = Object),
+        (status = 400, description = "Missing cookie, or a token without a JSON object payload")
+    )
+)]
+pub async fn cookie_payload(headers: HeaderMap) -> Response {
+    let Some(token) = gift_cookie(&headers) else {
+        return (StatusCode::BAD_REQUEST).into_response();
+    };
+    let claims = match token.split('.').collect::<Vec<_>>()[..] {
+        [_header, payload, _signature] if !payload.is_empty() => URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|payload| serde_json::from_slice::<serde_json::Value>(&payload).ok())
+            .filter(serde_json::Value::is_object),
+        _ => None,
+    };
+    let Some(claims) = claims else {
         return (StatusCode::BAD_REQUEST).into_response();
     };
 
-    (StatusCode::OK, token.claims.to_string()).into_response()
+    (
+        [(
+            X_SIGNATURE_NOT_VERIFIED.clone(),
+            HeaderValue::from_static("true"),
+        )],
+        Json(claims),
+    )
+        .into_response()
 }
 
-pub async fn decode(body: Bytes) -> Result<Json<serde_json::Value>, StatusCode> {
+/// Signature schemes the Santa key can verify
+const RSA_ALGORITHMS: [Algorithm; 3] = [Algorithm::RS256, Algorithm::RS384, Algorithm::RS512];
+
+#[utoipa::path(
+    post,
+    path = "/16/decode",
+    request_body(content = String, description = "RS256, RS384 or RS512 JWT", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Verified claims", body = Object),
+        (status = 400, description = "Malformed token, or signed with a non-RSA algorithm"),
+        (status = 401, description = "Invalid signature"),
+    )
+)]
+pub async fn decode(body: Bytes) -> Result<Json<serde_json::Value>, Response> {
     let jwt = String::from_utf8_lossy(&body);
-    dbg!(&jwt);
+    tracing::debug!(%jwt);
     let key = include_bytes!("../day16_santa_public_key.pem");
-    let header = decode_header(&jwt).map_err(|_| StatusCode::BAD_REQUEST)?;
-    dbg!(&header);
-    let mut validation = Validation::default();
-    validation.algorithms = vec![header.alg];
+    let key = DecodingKey::from_rsa_pem(key).unwrap();
+    verify_rsa(&jwt, &key).map(Json)
+}
+
+fn verify_rsa(jwt: &str, key: &DecodingKey) -> Result<serde_json::Value, Response> {
+    let header = decode_header(jwt).map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
+    tracing::debug!(?header);
+    if !RSA_ALGORITHMS.contains(&header.alg) {
+        return Err(AppError::UnsupportedAlgorithm(format!("{:?}", header.alg)).into_response());
+    }
+    let mut validation = Validation::new(header.alg);
+    validation.algorithms = RSA_ALGORITHMS.to_vec();
     validation.required_spec_claims.remove("exp");
 
-    let key = DecodingKey::from_rsa_pem(key).unwrap();
-    let token = jwt_decode(&jwt, &key, &validation).map_err(|e| match e.into_kind() {
-        ErrorKind::InvalidSignature => StatusCode::UNAUTHORIZED,
-        _ => StatusCode::BAD_REQUEST,
+    let token = jwt_decode(jwt, key, &validation).map_err(|e| match e.into_kind() {
+        ErrorKind::InvalidSignature => StatusCode::UNAUTHORIZED.into_response(),
+        _ => StatusCode::BAD_REQUEST.into_response(),
     })?;
-    Ok(Json(token.claims))
+    Ok(token.claims)
+}
+
+#[tokio::test]
+async fn test_unwrap_is_json() {
+    let claims = serde_json::json!({"gift": "socks", "nice": true});
+    let gift = serde_json::from_value(claims.clone()).unwrap();
+    let cookie = wrap(Query(WrapQuery { ttl: None }), Json(gift))
+        .await
+        .headers()[SET_COOKIE]
+        .clone();
+    let mut headers = HeaderMap::new();
+    headers.insert(COOKIE, cookie);
+
+    let res = unwrap(headers).await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()[axum::http::header::CONTENT_TYPE],
+        "application/json"
+    );
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body, claims.to_string());
+}
+
+#[cfg(test)]
+async fn wrapped_claims(ttl: Option<u64>, claims: serde_json::Value) -> serde_json::Value {
+    let query = Query(WrapQuery {
+        ttl: ttl.map(|ttl| NonZeroU64::new(ttl).unwrap()),
+    });
+    let claims = serde_json::from_value(claims).unwrap();
+    let cookie = wrap(query, Json(claims)).await.headers()[SET_COOKIE].clone();
+    let mut headers = HeaderMap::new();
+    headers.insert(COOKIE, cookie);
+    let body = axum::body::to_bytes(unwrap(headers).await.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_wrap_ttl() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let claims = wrapped_claims(Some(3600), serde_json::json!({"gift": "socks"})).await;
+    let exp = claims["exp"].as_u64().unwrap();
+    assert!((now + 3590..=now + 3610).contains(&exp), "exp = {exp}");
+    assert_eq!(claims["gift"], "socks");
+
+    let claims = wrapped_claims(Some(3600), serde_json::json!({"exp": 42})).await;
+    assert_eq!(claims["exp"], 42);
+
+    let claims = wrapped_claims(None, serde_json::json!({"gift": "socks"})).await;
+    assert!(claims.get("exp").is_none());
+}
+
+#[test]
+fn test_wrap_ttl_must_be_positive() {
+    let parse = |uri: &str| Query::<WrapQuery>::try_from_uri(&uri.parse().unwrap());
+
+    assert!(parse("/16/wrap?ttl=1").is_ok());
+    for uri in ["/16/wrap?ttl=0", "/16/wrap?ttl=-5", "/16/wrap?ttl=soon"] {
+        assert_eq!(parse(uri).err().unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_wrap_schema() {
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {"sub": {"type": "string"}},
+        "required": ["sub"]
+    });
+    let wrap_claims = |claims: serde_json::Value| {
+        let claims = serde_json::from_value(claims).unwrap();
+        wrap(Query(WrapQuery { ttl: None }), Json(claims))
+    };
+
+    let res = wrap_claims(serde_json::json!({"$schema": schema, "sub": "elf"})).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut headers = HeaderMap::new();
+    headers.insert(COOKIE, res.headers()[SET_COOKIE].clone());
+    let body = axum::body::to_bytes(unwrap(headers).await.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body, r#"{"sub":"elf"}"#);
+
+    for claims in [
+        serde_json::json!({"$schema": schema, "gift": "socks"}),
+        serde_json::json!({"$schema": schema, "sub": 7}),
+    ] {
+        let res = wrap_claims(claims).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!res.headers().contains_key(SET_COOKIE));
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+        assert_eq!(body["errors"][0]["status"], "422");
+    }
+
+    let res = wrap_claims(serde_json::json!({"$schema": "https://example.com/claims.json"})).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_gift_claims_round_trip() {
+    let claims = serde_json::json!({
+        "exp": 1_735_084_800,
+        "iat": 1_734_998_400,
+        "jti": "gift-7",
+        "recipient": {"name": "Rudolph", "nice": true},
+        "gifts": ["carrot", "bell"],
+    });
+
+    assert_eq!(wrapped_claims(None, claims.clone()).await, claims);
+
+    let parsed: GiftClaims = serde_json::from_value(claims).unwrap();
+    assert_eq!(parsed.jti.as_deref(), Some("gift-7"));
+    assert_eq!(parsed.extra["gifts"][1], "bell");
+}
+
+#[test]
+fn test_verify_rsa_algorithms() {
+    let signing_key =
+        EncodingKey::from_rsa_pem(include_bytes!("../day16_test_private_key.pem")).unwrap();
+    let key = DecodingKey::from_rsa_pem(include_bytes!("../day16_test_public_key.pem")).unwrap();
+    let claims = serde_json::json!({"reindeer": "Dasher", "nice": true});
+    let sign = |alg| encode(&Header::new(alg), &claims, &signing_key).unwrap();
+
+    for alg in RSA_ALGORITHMS {
+        assert_eq!(
+            verify_rsa(&sign(alg), &key).ok(),
+            Some(claims.clone()),
+            "{alg:?}"
+        );
+    }
+
+    // Signed by the test key, so Santa's key must refuse it
+    let santa = DecodingKey::from_rsa_pem(include_bytes!("../day16_santa_public_key.pem")).unwrap();
+    let res = verify_rsa(&sign(Algorithm::RS384), &santa).unwrap_err();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_decode_rejects_non_rsa_algorithms() {
+    let claims = serde_json::json!({"reindeer": "Dasher"});
+    let secret = EncodingKey::from_secret(SECRET);
+
+    for alg in [Algorithm::HS256, Algorithm::HS512] {
+        let jwt = encode(&Header::new(alg), &claims, &secret).unwrap();
+        let res = decode(Bytes::from(jwt)).await.unwrap_err();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["errors"][0]["detail"], "unsupported_algorithm");
+        assert_eq!(body["errors"][0]["meta"]["alg"], format!("{alg:?}"));
+    }
+
+    // The algorithm comes from the header alone, so a forged ES256 one is refused unverified
+    let jwt = encode(&Header::new(Algorithm::HS256), &claims, &secret).unwrap();
+    let forged = format!("eyJhbGciOiJFUzI1NiJ9{}", &jwt[jwt.find('.').unwrap()..]);
+    let res = decode(Bytes::from(forged)).await.unwrap_err();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[cfg(test)]
+fn gift_headers(jwt: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(COOKIE, format!("gift={jwt}").parse().unwrap());
+    headers
+}
+
+#[tokio::test]
+async fn test_cookie_payload_skips_verification() {
+    let claims = serde_json::json!({"gift": "socks", "nice": true});
+    let jwt = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SECRET),
+    )
+    .unwrap();
+    // Swap in a payload the signature doesn't cover
+    let tampered_claims = serde_json::json!({"gift": "coal"});
+    let mut parts: Vec<_> = jwt.split('.').map(str::to_owned).collect();
+    parts[1] = URL_SAFE_NO_PAD.encode(tampered_claims.to_string());
+    let tampered = parts.join(".");
+
+    for (jwt, expected) in [(jwt, claims), (tampered.clone(), tampered_claims)] {
+        let res = cookie_payload(gift_headers(&jwt)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[&X_SIGNATURE_NOT_VERIFIED], "true");
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            expected
+        );
+    }
+    assert_eq!(
+        unwrap(gift_headers(&tampered)).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn test_cookie_payload_rejects_malformed_tokens() {
+    let payload = URL_SAFE_NO_PAD.encode(r#"{"gift":"socks"}"#);
+    for jwt in [
+        "eyJhbGciOiJIUzI1NiJ9..sig".to_owned(),
+        format!("eyJhbGciOiJIUzI1NiJ9.{payload}"),
+        format!("a.{payload}.b.c"),
+        "a.not*base64.b".to_owned(),
+        format!("a.{}.b", URL_SAFE_NO_PAD.encode("[1, 2]")),
+    ] {
+        let res = cookie_payload(gift_headers(&jwt)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{jwt}");
+    }
+    assert_eq!(
+        cookie_payload(HeaderMap::new()).await.status(),
+        StatusCode::BAD_REQUEST
+    );
 }
@@ -1,11 +1,20 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    routing::{delete, get, post, put},
-    Json, Router,
+    body::Body,
+    extract::State,
+    http::{
+        header::{CONTENT_TYPE, HOST, IF_MATCH, LINK},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use sqlx::{
     prelude::FromRow,
@@ -15,9 +24,15 @@ use sqlx::{
     },
     PgPool,
 };
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{AppError, AppJson, AppPath, AppQuery};
+
+static IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
 
 /// Converts i64 to a 16-character hex string (uppercase).
 fn encode_page(page: i64) -> String {
+    debug_assert!(page >= 0, "page tokens must decode to a non-negative page");
     format!("{:016X}", page as u64)
 }
 
@@ -27,37 +42,330 @@ fn decode_page(token: &str) -> Option<i64> {
     Some(parsed as i64)
 }
 
-#[derive(Deserialize)]
+/// Encodes a keyset position and the page size it was issued for as base64url of
+/// `<rfc3339>/<uuid>/<page_size>`.
+fn encode_cursor(created_at: DateTime<Utc>, id: Uuid, page_size: i64) -> String {
+    let raw = format!("{}/{id}/{page_size}", created_at.to_rfc3339());
+    URL_SAFE_NO_PAD.encode(raw)
+}
+
+/// A row to continue after, by its sort key and id.
+type Position = (DateTime<Utc>, Uuid);
+
+/// Parses a cursor produced by [`encode_cursor`].
+fn decode_cursor(token: &str) -> Option<(Position, i64)> {
+    let raw = URL_SAFE_NO_PAD.decode(token).ok()?;
+    let raw = String::from_utf8(raw).ok()?;
+    let (position, page_size) = raw.rsplit_once('/')?;
+    let (created_at, id) = position.rsplit_once('/')?;
+    let created_at = DateTime::parse_from_rfc3339(created_at)
+        .ok()?
+        .with_timezone(&Utc);
+    let id = Uuid::from_str(id).ok()?;
+    let page_size = page_size.parse().ok()?;
+    Some(((created_at, id), page_size))
+}
+
+/// Encodes the last author on a page and the page size as base64url of `<page_size>/<author>`,
+/// with the author last since it may itself contain a `/`.
+fn encode_author_cursor(author: &str, page_size: i64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{page_size}/{author}"))
+}
+
+/// Parses a cursor produced by [`encode_author_cursor`].
+fn decode_author_cursor(token: &str) -> Option<(String, i64)> {
+    let raw = URL_SAFE_NO_PAD.decode(token).ok()?;
+    let raw = String::from_utf8(raw).ok()?;
+    let (page_size, author) = raw.split_once('/')?;
+    Some((author.to_owned(), page_size.parse().ok()?))
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct Payload {
     author: String,
     quote: String,
 }
 
-#[derive(FromRow, Serialize)]
+impl Payload {
+    /// Checks what the `quotes` table itself would accept silently.
+    fn validate(&self) -> Result<(), AppError> {
+        validate_fields(Some(&self.author), Some(&self.quote))
+    }
+}
+
+/// Fields of a [`Payload`], either of which may be left unchanged.
+#[derive(Deserialize, ToSchema)]
+pub struct PatchPayload {
+    author: Option<String>,
+    quote: Option<String>,
+}
+
+impl PatchPayload {
+    fn validate(&self) -> Result<(), AppError> {
+        if self.author.is_none() && self.quote.is_none() {
+            return Err(AppError::BadRequest(
+                "author or quote is required".to_owned(),
+            ));
+        }
+        validate_fields(self.author.as_deref(), self.quote.as_deref())
+    }
+}
+
+/// Runs the [`Payload`] checks on whichever fields are present.
+fn validate_fields(author: Option<&str>, quote: Option<&str>) -> Result<(), AppError> {
+    let failure = if author.is_some_and(|author| author.trim().is_empty()) {
+        "author must not be empty"
+    } else if quote.is_some_and(|quote| quote.trim().is_empty()) {
+        "quote must not be empty"
+    } else if author.is_some_and(|author| author.chars().count() > MAX_AUTHOR_CHARS) {
+        "author must be at most 255 characters"
+    } else if quote.is_some_and(|quote| quote.chars().count() > MAX_QUOTE_CHARS) {
+        "quote must be at most 4096 characters"
+    } else {
+        return Ok(());
+    };
+    Err(AppError::BadRequest(failure.to_owned()))
+}
+
+#[derive(FromRow, Serialize, ToSchema)]
 pub struct Quote {
     id: Uuid,
     author: String,
     quote: String,
     created_at: DateTime<Utc>,
     version: i32,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Quotes are the same quote when their ids match, whatever version of it each one holds.
+impl PartialEq for Quote {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
 }
 
-#[derive(Serialize)]
+impl Eq for Quote {}
+
+impl Hash for Quote {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.as_u128().hash(state);
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct Quotes {
     quotes: Vec<Quote>,
     page: i64,
+    total: i64,
+    total_pages: i64,
     next_token: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(FromRow, Serialize, ToSchema)]
+pub struct HistoryEntry {
+    version: i32,
+    author: String,
+    quote: String,
+    changed_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct History {
+    id: Uuid,
+    history: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, PartialEq, FromRow, Serialize, ToSchema)]
+pub struct AuthorCount {
+    author: String,
+    quote_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Authors {
+    authors: Vec<AuthorCount>,
+    next_token: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkInserted {
+    inserted: usize,
+    quotes: Vec<Quote>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListQuery {
-    token: String,
+    token: Option<String>,
+    /// Quotes per page, from 1 to 100; defaults to the size `token` was issued for, or 3
+    page_size: Option<u32>,
+}
+
+impl ListQuery {
+    /// The keyset position to continue after, if any, and the page size to use.
+    fn resolve(&self) -> Result<(Option<Position>, i64), AppError> {
+        let cursor = self
+            .token
+            .as_deref()
+            .map(|token| decode_cursor(token).ok_or_else(invalid_token))
+            .transpose()?;
+        let page_size = page_size(
+            self.page_size,
+            cursor.map(|(_, page_size)| page_size),
+            LIST_PAGE_SIZE,
+        )?;
+        Ok((cursor.map(|(position, _)| position), page_size))
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorsQuery {
+    token: Option<String>,
+    /// Authors per page, from 1 to 100; defaults to the size `token` was issued for, or 20
+    page_size: Option<u32>,
+}
+
+impl AuthorsQuery {
+    /// The author to continue after, if any, and the page size to use.
+    fn resolve(&self) -> Result<(Option<String>, i64), AppError> {
+        let cursor = self
+            .token
+            .as_deref()
+            .map(|token| decode_author_cursor(token).ok_or_else(invalid_token))
+            .transpose()?;
+        let (after, token_page_size) = cursor.unzip();
+        Ok((
+            after,
+            page_size(self.page_size, token_page_size, AUTHORS_PAGE_SIZE)?,
+        ))
+    }
+}
+
+/// An explicit `page_size` wins over the one a token was issued for, which wins over `default`.
+fn page_size(
+    requested: Option<u32>,
+    from_token: Option<i64>,
+    default: i64,
+) -> Result<i64, AppError> {
+    match (requested, from_token) {
+        (Some(page_size), _) if (1..=MAX_PAGE_SIZE).contains(&page_size) => {
+            Ok(i64::from(page_size))
+        }
+        (Some(_), _) => Err(AppError::BadRequest(format!(
+            "page_size must be between 1 and {MAX_PAGE_SIZE}"
+        ))),
+        (None, Some(page_size)) => Ok(page_size),
+        (None, None) => Ok(default),
+    }
 }
 
-fn uuid_from_str(s: &str) -> Result<Uuid, StatusCode> {
-    Uuid::from_str(s).map_err(|_| StatusCode::BAD_REQUEST)
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    q: Option<String>,
+    author: Option<String>,
+    token: Option<String>,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RandomQuery {
+    author: Option<String>,
+}
+
+const LIST_PAGE_SIZE: i64 = 3;
+const AUTHORS_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+const MAX_AUTHOR_CHARS: usize = 255;
+const MAX_QUOTE_CHARS: usize = 4096;
+const MAX_BULK_INSERT: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    format: String,
+}
+
+/// Quotes a CSV field per RFC 4180 when it contains a delimiter, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Builds an RFC 5988 `Link` value for `/19/list` from the request's host and forwarded scheme.
+fn list_links(headers: &HeaderMap, next_token: Option<&str>) -> Option<HeaderValue> {
+    let host = headers.get(HOST)?.to_str().ok()?;
+    let scheme = headers
+        .get("X-Forwarded-Proto")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or("http");
+
+    let mut links = format!("<{scheme}://{host}/19/list>; rel=\"first\"");
+    if let Some(token) = next_token {
+        links.push_str(&format!(
+            ", <{scheme}://{host}/19/list?token={token}>; rel=\"next\""
+        ));
+    }
+    HeaderValue::from_str(&links).ok()
+}
+
+fn uuid_from_str(s: &str) -> Result<Uuid, AppError> {
+    Uuid::from_str(s).map_err(|_| AppError::BadRequest(format!("Invalid quote id: {s}")))
+}
+
+/// No live quote has this id.
+#[derive(Debug, PartialEq)]
+pub struct QuoteError(Uuid);
+
+impl From<QuoteError> for AppError {
+    fn from(QuoteError(id): QuoteError) -> Self {
+        AppError::MissingId("quote not found", id)
+    }
+}
+
+impl IntoResponse for QuoteError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+/// Number of pages of `page_size` needed for `total` quotes; zero when there are none.
+fn page_count(total: i64, page_size: i64) -> i64 {
+    (total + page_size - 1) / page_size
+}
+
+fn invalid_token() -> AppError {
+    AppError::BadRequest("Invalid page token".to_owned())
+}
+
+/// Logs a database failure and reports it without leaking the error text.
+fn query_failed(e: sqlx::Error) -> AppError {
+    tracing::error!(error = %e, "query failed");
+    AppError::ServiceUnavailable
+}
+
+/// The `quotes` length checks catch what slips past [`Payload::validate`], as in `undo`.
+fn constraint_violation(e: &sqlx::Error) -> Option<AppError> {
+    match e {
+        // check_violation
+        sqlx::Error::Database(db) if db.code().as_deref() == Some("23514") => Some(
+            AppError::ConstraintViolation(db.constraint().unwrap_or_default().to_owned()),
+        ),
+        _ => None,
+    }
+}
+
+fn write_failed(e: sqlx::Error) -> AppError {
+    constraint_violation(&e).unwrap_or_else(|| query_failed(e))
+}
+
+#[utoipa::path(post, path = "/19/reset", responses((status = 200, description = "All quotes deleted")))]
 pub async fn reset(State(pool): State<PgPool>) {
     sqlx::query("DELETE FROM quotes")
         .execute(&pool)
@@ -65,71 +373,251 @@ pub async fn reset(State(pool): State<PgPool>) {
         .unwrap();
 }
 
+#[utoipa::path(get, path = "/19/cite/{id}", params(("id" = Uuid, Path, description = "Quote id")), responses((status = 200, body = Quote), (status = 400, description = "Malformed id"), (status = 404, description = "No such quote")))]
 pub async fn cite(
     State(pool): State<PgPool>,
-    Path(id): Path<String>,
-) -> Result<Json<Quote>, StatusCode> {
+    AppPath(id): AppPath<String>,
+) -> Result<Json<Quote>, AppError> {
     let id = uuid_from_str(&id)?;
     sqlx::query_as(
         r#"
         SELECT id, author, quote, created_at, version
         FROM quotes
-        WHERE id = $1
+        WHERE id = $1 AND deleted_at IS NULL
         "#,
     )
     .bind(id)
-    .fetch_one(&pool)
+    .fetch_optional(&pool)
+    .await
+    .map_err(query_failed)?
+    .map(Json)
+    .ok_or_else(|| QuoteError(id).into())
+}
+
+#[utoipa::path(
+    patch,
+    path = "/19/cite/{id}",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = PatchPayload,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, description = "Malformed id, neither field given, or a blank or overlong one"),
+        (status = 404, description = "No such quote")
+    )
+)]
+pub async fn amend(
+    State(pool): State<PgPool>,
+    AppPath(id): AppPath<String>,
+    AppJson(payload): AppJson<PatchPayload>,
+) -> Result<Json<Quote>, AppError> {
+    let id = uuid_from_str(&id)?;
+    payload.validate()?;
+
+    let updated: Option<Quote> = sqlx::query_as(
+        r#"
+        UPDATE quotes
+        SET author = COALESCE($1, author), quote = COALESCE($2, quote), version = version+1
+        WHERE id = $3 AND deleted_at IS NULL
+        RETURNING id, author, quote, created_at, version
+        "#,
+    )
+    .bind(payload.author)
+    .bind(payload.quote)
+    .bind(id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(write_failed)?;
+    updated.map(Json).ok_or_else(|| QuoteError(id).into())
+}
+
+#[utoipa::path(get, path = "/19/cite/{id}/history", params(("id" = Uuid, Path, description = "Quote id")), responses((status = 200, body = History), (status = 400, description = "Malformed id"), (status = 404, description = "No such quote")))]
+pub async fn history(
+    State(pool): State<PgPool>,
+    AppPath(id): AppPath<String>,
+) -> Result<Json<History>, AppError> {
+    let id = uuid_from_str(&id)?;
+    // Every version is recorded by the `quotes_history` trigger, including the current one
+    let history: Vec<HistoryEntry> = sqlx::query_as(
+        r#"
+        SELECT version, author, quote, changed_at
+        FROM quote_history
+        WHERE quote_id = $1
+        ORDER BY version ASC
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&pool)
+    .await
+    .map_err(query_failed)?;
+
+    if history.is_empty() {
+        return Err(AppError::NotFound);
+    }
+
+    Ok(Json(History { id, history }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/19/cite/{id}/history/{version}",
+    params(("id" = Uuid, Path, description = "Quote id"), ("version" = i32, Path, description = "Version number")),
+    responses((status = 200, body = HistoryEntry), (status = 400, description = "Malformed id"), (status = 404, description = "No such quote or version"))
+)]
+pub async fn history_version(
+    State(pool): State<PgPool>,
+    AppPath((id, version)): AppPath<(String, i32)>,
+) -> Result<Json<HistoryEntry>, AppError> {
+    let id = uuid_from_str(&id)?;
+    sqlx::query_as(
+        r#"
+        SELECT version, author, quote, changed_at
+        FROM quote_history
+        WHERE quote_id = $1 AND version = $2
+        "#,
+    )
+    .bind(id)
+    .bind(version)
+    .fetch_optional(&pool)
     .await
+    .map_err(query_failed)?
     .map(Json)
-    .map_err(|_| StatusCode::NOT_FOUND)
+    .ok_or(AppError::NotFound)
 }
 
+#[utoipa::path(delete, path = "/19/remove/{id}", params(("id" = Uuid, Path, description = "Quote id")), responses((status = 200, description = "Quote moved to the trash", body = Quote), (status = 400, description = "Malformed id"), (status = 404, description = "No such quote")))]
 pub async fn remove(
     State(pool): State<PgPool>,
-    Path(id): Path<String>,
-) -> Result<Json<Quote>, StatusCode> {
+    AppPath(id): AppPath<String>,
+) -> Result<Json<Quote>, AppError> {
     let id = uuid_from_str(&id)?;
     sqlx::query_as(
         r#"
-        DELETE FROM quotes
-        WHERE id = $1
+        UPDATE quotes
+        SET deleted_at = now()
+        WHERE id = $1 AND deleted_at IS NULL
         RETURNING id, author, quote, created_at, version
         "#,
     )
     .bind(id)
-    .fetch_one(&pool)
+    .fetch_optional(&pool)
     .await
+    .map_err(query_failed)?
     .map(Json)
-    .map_err(|_| StatusCode::NOT_FOUND)
+    .ok_or_else(|| QuoteError(id).into())
 }
 
+#[utoipa::path(
+    put,
+    path = "/19/undo/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Quote id"),
+        ("If-Match" = Option<i32>, Header, description = "Only update if the quote is still at this version")
+    ),
+    request_body = Payload,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, description = "Malformed id or `If-Match`, or a blank or overlong author or quote"),
+        (status = 404, description = "No such quote"),
+        (status = 409, description = "The quote has moved past the `If-Match` version")
+    )
+)]
 pub async fn undo(
     State(pool): State<PgPool>,
-    Path(id): Path<String>,
-    Json(payload): Json<Payload>,
-) -> Result<Json<Quote>, StatusCode> {
+    AppPath(id): AppPath<String>,
+    headers: HeaderMap,
+    AppJson(payload): AppJson<Payload>,
+) -> Result<Json<Quote>, AppError> {
     let id = uuid_from_str(&id)?;
-    sqlx::query_as(
+    // Accepted bare or quoted like an entity tag
+    let expected_version = headers
+        .get(IF_MATCH)
+        .map(|version| {
+            version
+                .to_str()
+                .ok()
+                .and_then(|version| version.trim_matches('"').parse::<i32>().ok())
+                .ok_or_else(|| AppError::BadRequest("Invalid If-Match version".to_owned()))
+        })
+        .transpose()?;
+
+    let updated: Option<Quote> = sqlx::query_as(
         r#"
         UPDATE quotes
         SET author = $1, quote = $2, version = version+1
-        WHERE id = $3
+        WHERE id = $3 AND deleted_at IS NULL
+          AND ($4::int IS NULL OR version = $4)
         RETURNING id, author, quote, created_at, version
         "#,
     )
     .bind(payload.author)
     .bind(payload.quote)
-    .bind(&id)
-    .fetch_one(&pool)
+    .bind(id)
+    .bind(expected_version)
+    .fetch_optional(&pool)
     .await
+    .map_err(write_failed)?;
+    if let Some(quote) = updated {
+        return Ok(Json(quote));
+    }
+
+    // Nothing matched: either the quote is gone or it is at another version
+    let current: Option<(i32,)> =
+        sqlx::query_as("SELECT version FROM quotes WHERE id = $1 AND deleted_at IS NULL")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(query_failed)?;
+    match current {
+        Some((version,)) => Err(AppError::VersionConflict(version)),
+        None => Err(QuoteError(id).into()),
+    }
+}
+
+#[utoipa::path(put, path = "/19/restore/{id}", params(("id" = Uuid, Path, description = "Quote id")), responses((status = 200, description = "Quote taken out of the trash", body = Quote), (status = 400, description = "Malformed id"), (status = 404, description = "No such quote in the trash")))]
+pub async fn restore(
+    State(pool): State<PgPool>,
+    AppPath(id): AppPath<String>,
+) -> Result<Json<Quote>, AppError> {
+    let id = uuid_from_str(&id)?;
+    sqlx::query_as(
+        r#"
+        UPDATE quotes
+        SET deleted_at = NULL
+        WHERE id = $1 AND deleted_at IS NOT NULL
+        RETURNING id, author, quote, created_at, version
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(query_failed)?
     .map(Json)
-    .map_err(|_| StatusCode::NOT_FOUND)
+    .ok_or_else(|| QuoteError(id).into())
 }
 
+#[utoipa::path(
+    post,
+    path = "/19/draft",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the quote drafted with this key in the last 24 hours instead of inserting again")),
+    request_body = Payload,
+    responses((status = 201, body = Quote), (status = 400, description = "Blank or overlong author or quote"))
+)]
 pub async fn draft(
     State(pool): State<PgPool>,
-    Json(payload): Json<Payload>,
-) -> (StatusCode, Json<Quote>) {
+    headers: HeaderMap,
+    AppJson(payload): AppJson<Payload>,
+) -> Result<(StatusCode, Json<Quote>), AppError> {
+    payload.validate()?;
+    let key = headers
+        .get(&IDEMPOTENCY_KEY)
+        .map(|key| key.to_str())
+        .transpose()
+        .map_err(|_| AppError::BadRequest("Invalid Idempotency-Key".to_owned()))?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| AppError::ServiceUnavailable)?;
     let quote: Quote = sqlx::query_as(
         r#"
         INSERT INTO quotes (id, author, quote)
@@ -140,34 +628,422 @@ pub async fn draft(
     .bind(Uuid::new_v4())
     .bind(payload.author)
     .bind(payload.quote)
-    .fetch_one(&pool)
+    .fetch_one(&mut *tx)
     .await
-    .unwrap();
+    .map_err(write_failed)?;
+
+    if let Some(key) = key {
+        // Claims the key unless a live entry holds it. A concurrent request with the same key
+        // blocks on the row until this transaction ends, so only one of them inserts
+        let claimed: Option<(Uuid,)> = sqlx::query_as(
+            r#"
+            INSERT INTO idempotency_cache (key, quote_id)
+            VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE
+            SET quote_id = EXCLUDED.quote_id, created_at = now()
+            WHERE idempotency_cache.created_at <= now() - INTERVAL '24 hours'
+            RETURNING quote_id
+            "#,
+        )
+        .bind(key)
+        .bind(quote.id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(query_failed)?;
+
+        if claimed.is_none() {
+            tx.rollback().await.map_err(query_failed)?;
+            let original = sqlx::query_as(
+                r#"
+                SELECT q.id, q.author, q.quote, q.created_at, q.version
+                FROM quotes q
+                JOIN idempotency_cache c ON c.quote_id = q.id
+                WHERE c.key = $1
+                "#,
+            )
+            .bind(key)
+            .fetch_one(&pool)
+            .await
+            .map_err(query_failed)?;
+            return Ok((StatusCode::CREATED, Json(original)));
+        }
+    }
+
+    tx.commit().await.map_err(query_failed)?;
+    Ok((StatusCode::CREATED, Json(quote)))
+}
+
+#[utoipa::path(post, path = "/19/bulk", request_body = Vec<Payload>, responses((status = 201, body = BulkInserted), (status = 413, description = "More than 100 quotes")))]
+pub async fn bulk(
+    State(pool): State<PgPool>,
+    AppJson(payloads): AppJson<Vec<Payload>>,
+) -> Result<(StatusCode, Json<BulkInserted>), AppError> {
+    if payloads.len() > MAX_BULK_INSERT {
+        return Err(AppError::PayloadTooLarge);
+    }
+    payloads.iter().try_for_each(Payload::validate)?;
+
+    let (ids, (authors, quotes)): (Vec<Uuid>, (Vec<String>, Vec<String>)) = payloads
+        .into_iter()
+        .map(|p| (Uuid::new_v4(), (p.author, p.quote)))
+        .unzip();
+
+    // Dropping the transaction without committing rolls the whole batch back
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| AppError::ServiceUnavailable)?;
+    let quotes: Vec<Quote> = sqlx::query_as(
+        r#"
+        INSERT INTO quotes (id, author, quote)
+        SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::text[])
+        RETURNING id, author, quote, created_at, version
+        "#,
+    )
+    .bind(ids)
+    .bind(authors)
+    .bind(quotes)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(write_failed)?;
+    tx.commit().await.map_err(query_failed)?;
 
-    (StatusCode::CREATED, Json(quote))
+    Ok((
+        StatusCode::CREATED,
+        Json(BulkInserted {
+            inserted: quotes.len(),
+            quotes,
+        }),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/19/list",
+    params(ListQuery),
+    responses(
+        (status = 200, body = Quotes, headers(("Link" = String, description = "`first` and `next` pages"))),
+        (status = 400, description = "Malformed token or `page_size` out of range"),
+    )
+)]
 pub async fn list(
     State(pool): State<PgPool>,
-    query: Option<Query<ListQuery>>,
-) -> Result<Json<Quotes>, StatusCode> {
-    // If a token is provided, decode the page number; otherwise start at page 0.
-    let page_number = if let Some(Query(query)) = query {
-        decode_page(&query.token).ok_or(StatusCode::BAD_REQUEST)?
+    headers: HeaderMap,
+    AppQuery(query): AppQuery<ListQuery>,
+) -> Result<(HeaderMap, Json<Quotes>), AppError> {
+    // `/19/list` always answered a failed query with 400, and the switch to `AppError` kept it
+    let quotes = list_page(&pool, &query, None).await.map_err(|e| match e {
+        AppError::ServiceUnavailable => AppError::BadRequest("Query failed".to_owned()),
+        e => e,
+    })?;
+
+    let mut response_headers = HeaderMap::new();
+    if let Some(links) = list_links(&headers, quotes.next_token.as_deref()) {
+        response_headers.insert(LINK, links);
+    }
+
+    Ok((response_headers, Json(quotes)))
+}
+
+/// One page of live quotes in creation order, optionally only those by `author`.
+async fn list_page(
+    pool: &PgPool,
+    query: &ListQuery,
+    author: Option<&str>,
+) -> Result<Quotes, AppError> {
+    // If a token is provided, continue after that row; otherwise start at the beginning.
+    let (cursor, page_size) = query.resolve()?;
+    let (after_created_at, after_id) = cursor.unzip();
+
+    // Count total quotes in the table, and how many come before the cursor
+    let (count, preceding): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            COUNT(id),
+            COUNT(id) FILTER (WHERE (created_at, id) <= ($1, $2))
+        FROM quotes
+        WHERE deleted_at IS NULL
+          AND ($3::text IS NULL OR author = $3)
+        "#,
+    )
+    .bind(after_created_at)
+    .bind(after_id)
+    .bind(author)
+    .fetch_one(pool)
+    .await
+    .map_err(query_failed)?;
+
+    let quotes: Vec<Quote> = sqlx::query_as(
+        r#"
+        SELECT id, author, quote, created_at, version
+        FROM quotes
+        WHERE deleted_at IS NULL
+          AND ($2::timestamptz IS NULL OR (created_at, id) > ($2, $3))
+          AND ($4::text IS NULL OR author = $4)
+        ORDER BY created_at ASC, id ASC
+        LIMIT $1
+        "#,
+    )
+    .bind(page_size)
+    .bind(after_created_at)
+    .bind(after_id)
+    .bind(author)
+    .fetch_all(pool)
+    .await
+    .map_err(query_failed)?;
+
+    // Only generate a next token if there are more rows after this page
+    let next_token = match quotes.last() {
+        Some(last) if preceding + (quotes.len() as i64) < count => {
+            Some(encode_cursor(last.created_at, last.id, page_size))
+        }
+        _ => None,
+    };
+
+    Ok(Quotes {
+        quotes,
+        page: preceding / page_size + 1,
+        total: count,
+        total_pages: page_count(count, page_size),
+        next_token,
+    })
+}
+
+#[utoipa::path(get, path = "/19/authors", params(AuthorsQuery), responses((status = 200, body = Authors), (status = 400, description = "Malformed token or `page_size` out of range")))]
+pub async fn authors(
+    State(pool): State<PgPool>,
+    AppQuery(query): AppQuery<AuthorsQuery>,
+) -> Result<Json<Authors>, AppError> {
+    let (after, page_size) = query.resolve()?;
+
+    // One row past the page tells whether there is a next one
+    let mut authors: Vec<AuthorCount> = sqlx::query_as(
+        r#"
+        SELECT author, COUNT(*) AS quote_count
+        FROM quotes
+        WHERE deleted_at IS NULL
+          AND ($2::text IS NULL OR author > $2)
+        GROUP BY author
+        ORDER BY author ASC
+        LIMIT $1
+        "#,
+    )
+    .bind(page_size + 1)
+    .bind(after)
+    .fetch_all(&pool)
+    .await
+    .map_err(query_failed)?;
+
+    let next_token = if authors.len() as i64 > page_size {
+        authors.truncate(page_size as usize);
+        authors
+            .last()
+            .map(|last| encode_author_cursor(&last.author, page_size))
     } else {
-        0
+        None
+    };
+
+    Ok(Json(Authors {
+        authors,
+        next_token,
+    }))
+}
+
+#[utoipa::path(get, path = "/19/authors/{author}", params(("author" = String, Path, description = "Exact author name"), ListQuery), responses((status = 200, body = Quotes), (status = 400, description = "Malformed token or `page_size` out of range")))]
+pub async fn author_quotes(
+    State(pool): State<PgPool>,
+    AppPath(author): AppPath<String>,
+    AppQuery(query): AppQuery<ListQuery>,
+) -> Result<Json<Quotes>, AppError> {
+    list_page(&pool, &query, Some(&author)).await.map(Json)
+}
+
+#[utoipa::path(get, path = "/19/trash", params(ListQuery), responses((status = 200, body = Quotes), (status = 400, description = "Malformed token or `page_size` out of range")))]
+pub async fn trash(
+    State(pool): State<PgPool>,
+    AppQuery(query): AppQuery<ListQuery>,
+) -> Result<Json<Quotes>, AppError> {
+    // Same keyset pagination as `list`, but walking backwards from the most recent deletion
+    let (cursor, page_size) = query.resolve()?;
+    let (after_deleted_at, after_id) = cursor.unzip();
+
+    let (count, preceding): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            COUNT(id),
+            COUNT(id) FILTER (WHERE (deleted_at, id) >= ($1, $2))
+        FROM quotes
+        WHERE deleted_at IS NOT NULL
+        "#,
+    )
+    .bind(after_deleted_at)
+    .bind(after_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(query_failed)?;
+
+    let quotes: Vec<Quote> = sqlx::query_as(
+        r#"
+        SELECT id, author, quote, created_at, version, deleted_at
+        FROM quotes
+        WHERE deleted_at IS NOT NULL
+          AND ($2::timestamptz IS NULL OR (deleted_at, id) < ($2, $3))
+        ORDER BY deleted_at DESC, id DESC
+        LIMIT $1
+        "#,
+    )
+    .bind(page_size)
+    .bind(after_deleted_at)
+    .bind(after_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(query_failed)?;
+
+    let next_token = match quotes.last() {
+        Some(Quote {
+            id,
+            deleted_at: Some(deleted_at),
+            ..
+        }) if preceding + (quotes.len() as i64) < count => {
+            Some(encode_cursor(*deleted_at, *id, page_size))
+        }
+        _ => None,
     };
 
-    let offset = page_number * 3;
+    Ok(Json(Quotes {
+        quotes,
+        page: preceding / page_size + 1,
+        total: count,
+        total_pages: page_count(count, page_size),
+        next_token,
+    }))
+}
 
-    // Count total quotes in the table
-    let (count,): (i64,) = sqlx::query_as(r"SELECT COUNT(id) FROM quotes")
-        .fetch_one(&pool)
-        .await
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+#[utoipa::path(get, path = "/19/random", params(RandomQuery), responses((status = 200, body = Quote), (status = 404, description = "No matching quotes")))]
+pub async fn random(
+    State(pool): State<PgPool>,
+    AppQuery(query): AppQuery<RandomQuery>,
+) -> Result<Json<Quote>, AppError> {
+    sqlx::query_as(
+        r#"
+        SELECT id, author, quote, created_at, version
+        FROM quotes
+        WHERE deleted_at IS NULL
+          AND ($1::text IS NULL OR lower(author) = lower($1))
+        ORDER BY random()
+        LIMIT 1
+        "#,
+    )
+    .bind(query.author)
+    .fetch_optional(&pool)
+    .await
+    .map_err(query_failed)?
+    .map(Json)
+    .ok_or(AppError::NotFound)
+}
 
-    // Only generate a next token if there are more pages
-    let next_token = if offset + 3 >= count {
+#[utoipa::path(
+    get,
+    path = "/19/export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Every quote", content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 415, description = "Unknown format"),
+    )
+)]
+pub async fn export(
+    State(pool): State<PgPool>,
+    AppQuery(query): AppQuery<ExportQuery>,
+) -> Response {
+    let (content_type, header, render): (_, _, fn(Quote) -> String) = match query.format.as_str() {
+        "json" => ("application/x-ndjson", None, |quote| {
+            let mut line = serde_json::to_string(&quote).unwrap();
+            line.push('\n');
+            line
+        }),
+        "csv" => (
+            "text/csv",
+            Some("id,author,quote,created_at,version\r\n"),
+            |quote| {
+                format!(
+                    "{},{},{},{},{}\r\n",
+                    quote.id,
+                    csv_field(&quote.author),
+                    csv_field(&quote.quote),
+                    quote.created_at.to_rfc3339(),
+                    quote.version
+                )
+            },
+        ),
+        _ => return AppError::UnsupportedMediaType.into_response(),
+    };
+
+    // Rows are rendered as they arrive so memory does not grow with the table size
+    let stream = async_stream::stream! {
+        if let Some(header) = header {
+            yield Ok(header.to_owned());
+        }
+        let mut rows = sqlx::query_as::<_, Quote>(
+            r#"
+            SELECT id, author, quote, created_at, version
+            FROM quotes
+            WHERE deleted_at IS NULL
+            ORDER BY created_at ASC, id ASC
+            "#,
+        )
+        .fetch(&pool);
+        while let Some(row) = rows.next().await {
+            yield row.map(render);
+        }
+    };
+
+    ([(CONTENT_TYPE, content_type)], Body::from_stream(stream)).into_response()
+}
+
+#[utoipa::path(get, path = "/19/search", params(SearchQuery), responses((status = 200, body = Quotes), (status = 400, description = "Neither `q` nor `author` given, or malformed token")))]
+pub async fn search(
+    State(pool): State<PgPool>,
+    AppQuery(query): AppQuery<SearchQuery>,
+) -> Result<Json<Quotes>, AppError> {
+    if query.q.is_none() && query.author.is_none() {
+        return Err(AppError::BadRequest(
+            "Expected at least one of `q` or `author`".to_owned(),
+        ));
+    }
+
+    // Unlike `list`, results are ordered by a computed, floating-point rank, which a keyset
+    // cursor couldn't carry exactly, so search keeps page-number tokens and an `OFFSET`. The
+    // `id` in the `ORDER BY` keeps that order total, so pages don't overlap or skip rows
+    let page_number = match &query.token {
+        Some(token) => decode_page(token).ok_or_else(invalid_token)?,
+        None => 0,
+    };
+
+    // A crafted token may wrap around to a negative page, which would make `OFFSET` negative
+    if page_number < 0 {
+        return Err(invalid_token());
+    }
+    let offset = page_number
+        .checked_mul(LIST_PAGE_SIZE)
+        .ok_or_else(invalid_token)?;
+
+    // Both filters are optional; a NULL parameter disables its clause
+    let (count,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(id)
+        FROM quotes
+        WHERE deleted_at IS NULL
+          AND ($1::text IS NULL OR to_tsvector('english', quote) @@ plainto_tsquery('english', $1))
+          AND ($2::text IS NULL OR lower(author) = lower($2))
+        "#,
+    )
+    .bind(&query.q)
+    .bind(&query.author)
+    .fetch_one(&pool)
+    .await
+    .map_err(query_failed)?;
+
+    let next_token = if offset + LIST_PAGE_SIZE >= count {
         None
     } else {
         Some(encode_page(page_number + 1))
@@ -177,19 +1053,1091 @@ pub async fn list(
         r#"
         SELECT id, author, quote, created_at, version
         FROM quotes
-        ORDER BY created_at ASC
-        LIMIT 3
-        OFFSET $1
+        WHERE deleted_at IS NULL
+          AND ($1::text IS NULL OR to_tsvector('english', quote) @@ plainto_tsquery('english', $1))
+          AND ($2::text IS NULL OR lower(author) = lower($2))
+        ORDER BY ts_rank(to_tsvector('english', quote), plainto_tsquery('english', COALESCE($1, ''))) DESC,
+                 created_at ASC,
+                 id ASC
+        LIMIT $3
+        OFFSET $4
         "#,
     )
+    .bind(&query.q)
+    .bind(&query.author)
+    .bind(LIST_PAGE_SIZE)
     .bind(offset)
     .fetch_all(&pool)
     .await
-    .map_err(|_| StatusCode::BAD_REQUEST)?;
+    .map_err(query_failed)?;
 
     Ok(Json(Quotes {
         quotes,
         page: page_number + 1,
+        total: count,
+        total_pages: page_count(count, LIST_PAGE_SIZE),
         next_token,
     }))
 }
+
+#[tokio::test]
+async fn test_bulk_rejects_over_cap() {
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    let payloads = (0..=MAX_BULK_INSERT)
+        .map(|i| Payload {
+            author: format!("author {i}"),
+            quote: format!("quote {i}"),
+        })
+        .collect();
+
+    let res = bulk(State(pool), AppJson(payloads)).await;
+
+    assert_eq!(res.err(), Some(AppError::PayloadTooLarge));
+}
+
+#[cfg(test)]
+async fn error_document(res: Response) -> serde_json::Value {
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/vnd.api+json");
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// A pool whose every query fails quickly, for the database-outage paths.
+#[cfg(test)]
+fn unreachable_pool() -> PgPool {
+    sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_millis(100))
+        .connect_lazy("postgres://localhost:1/unused")
+        .unwrap()
+}
+
+/// `cite`, `remove` and `undo` for the same id, in that order.
+#[cfg(test)]
+async fn lookups(pool: &PgPool, id: Uuid) -> [Result<Json<Quote>, AppError>; 3] {
+    let payload = Payload {
+        author: "Santa".to_owned(),
+        quote: "Ho ho ho".to_owned(),
+    };
+    [
+        cite(State(pool.clone()), AppPath(id.to_string())).await,
+        remove(State(pool.clone()), AppPath(id.to_string())).await,
+        undo(
+            State(pool.clone()),
+            AppPath(id.to_string()),
+            HeaderMap::new(),
+            AppJson(payload),
+        )
+        .await,
+    ]
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_missing_quote_error() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let id = Uuid::new_v4();
+
+    let responses = lookups(&db.pool, id).await;
+
+    db.remove().await;
+    for res in responses {
+        let res = res.into_response();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let errors = &error_document(res).await["errors"];
+        assert_eq!(errors[0]["status"], "404");
+        assert_eq!(errors[0]["title"], "Not Found");
+        assert_eq!(errors[0]["detail"], "quote not found");
+        assert_eq!(errors[0]["meta"]["id"], id.to_string());
+    }
+}
+
+#[tokio::test]
+async fn test_unreachable_db_is_not_a_missing_quote() {
+    for res in lookups(&unreachable_pool(), Uuid::new_v4()).await {
+        let res = res.into_response();
+        assert!(res.status().is_server_error(), "{}", res.status());
+    }
+}
+
+#[tokio::test]
+async fn test_draft_malformed_body_error() {
+    use axum::{http::Request, routing::post, Router};
+    use tower::ServiceExt;
+
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    let router = Router::new()
+        .route("/19/draft", post(draft))
+        .with_state(pool);
+    let req = Request::post("/19/draft")
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"author": "Santa""#))
+        .unwrap();
+
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let errors = &error_document(res).await["errors"];
+    assert_eq!(errors[0]["status"], "400");
+    assert_eq!(errors[0]["title"], "Bad Request");
+    assert!(errors[0]["detail"]
+        .as_str()
+        .unwrap()
+        .contains("Failed to parse the request body as JSON"));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_bulk_is_atomic() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    // A constraint `Payload::validate` knows nothing about, so the bad row reaches the insert
+    sqlx::query("ALTER TABLE quotes ADD CONSTRAINT no_grinch CHECK (author <> 'Grinch')")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let payloads = ["Santa", "Grinch", "Rudolph"]
+        .into_iter()
+        .map(|author| Payload {
+            author: author.to_owned(),
+            quote: "Ho".to_owned(),
+        })
+        .collect();
+
+    let res = bulk(State(db.pool.clone()), AppJson(payloads)).await;
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM quotes")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+
+    db.remove().await;
+    assert_eq!(
+        res.err(),
+        Some(AppError::ConstraintViolation("no_grinch".to_owned()))
+    );
+    assert_eq!(count, 0);
+}
+
+/// Inserts live quotes one second apart, in order, and returns their ids.
+#[cfg(test)]
+async fn insert_quotes(pool: &PgPool, quotes: &[(&str, &str)]) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+    for (i, (author, quote)) in quotes.iter().enumerate() {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO quotes (id, author, quote, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(author)
+            .bind(quote)
+            .bind(DateTime::from_timestamp(i as i64, 0))
+            .execute(pool)
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+    ids
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_search() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let ids = insert_quotes(
+        &db.pool,
+        &[
+            ("Santa", "Ho ho ho, merry Christmas"),
+            ("Rudolph", "My nose glows for Christmas"),
+            ("Santa", "The sleigh is packed"),
+            ("100%_Elf", "Wrapping gifts all night"),
+        ],
+    )
+    .await;
+    let search_for = |q: Option<&str>, author: Option<&str>| {
+        let query = SearchQuery {
+            q: q.map(str::to_owned),
+            author: author.map(str::to_owned),
+            token: None,
+        };
+        search(State(db.pool.clone()), AppQuery(query))
+    };
+    let found = |quotes: Quotes| quotes.quotes.into_iter().map(|q| q.id).collect::<Vec<_>>();
+
+    let christmas = found(search_for(Some("christmas"), None).await.unwrap().0);
+    let santa = found(search_for(None, Some("SANTA")).await.unwrap().0);
+    let both = found(
+        search_for(Some("christmas"), Some("santa"))
+            .await
+            .unwrap()
+            .0,
+    );
+    let wildcard = found(search_for(None, Some("%")).await.unwrap().0);
+    let underscore = found(search_for(None, Some("100%_elf")).await.unwrap().0);
+
+    db.remove().await;
+    assert_eq!(christmas.len(), 2);
+    assert!(christmas.contains(&ids[0]) && christmas.contains(&ids[1]));
+    assert_eq!(santa, [ids[0], ids[2]]);
+    assert_eq!(both, [ids[0]]);
+    // The author filter is an exact match, so pattern characters only match themselves
+    assert!(wildcard.is_empty());
+    assert_eq!(underscore, [ids[3]]);
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_search_pages_through_ties() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    // One `/19/bulk` transaction gives every row the same `created_at`, and equal quotes rank
+    // the same, so only the id tells these apart
+    let payloads = (0..7)
+        .map(|_| Payload {
+            author: "Santa".to_owned(),
+            quote: "Merry Christmas".to_owned(),
+        })
+        .collect();
+    let (_, Json(inserted)) = bulk(State(db.pool.clone()), AppJson(payloads))
+        .await
+        .unwrap();
+
+    let mut found = Vec::new();
+    let mut token = None;
+    for _ in 0..3 {
+        let query = SearchQuery {
+            q: Some("christmas".to_owned()),
+            author: None,
+            token,
+        };
+        let Json(page) = search(State(db.pool.clone()), AppQuery(query))
+            .await
+            .unwrap();
+        found.extend(page.quotes.into_iter().map(|quote| quote.id));
+        token = page.next_token;
+    }
+
+    db.remove().await;
+    assert_eq!(token, None);
+    let mut ids = inserted
+        .quotes
+        .into_iter()
+        .map(|quote| quote.id)
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(found, ids);
+}
+
+#[tokio::test]
+async fn test_search_rejects_negative_page_token() {
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    let query = SearchQuery {
+        q: Some("christmas".to_owned()),
+        author: None,
+        // What `encode_page(-1)` would have produced
+        token: Some("FFFFFFFFFFFFFFFF".to_owned()),
+    };
+
+    let res = search(State(pool), AppQuery(query)).await;
+
+    assert_eq!(res.err(), Some(invalid_token()));
+}
+
+#[test]
+fn test_page_token_round_trip() {
+    for page in [0, 1, 41, i64::MAX] {
+        let token = encode_page(page);
+        assert_eq!(token.len(), 16);
+        assert_eq!(decode_page(&token), Some(page));
+    }
+}
+
+#[tokio::test]
+async fn test_draft_validation() {
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    let payload = |author: &str, quote: String| Payload {
+        author: author.to_owned(),
+        quote,
+    };
+    let cases = [
+        (
+            payload("", "Ho ho ho".to_owned()),
+            "author must not be empty",
+        ),
+        (
+            payload("Santa", " \n\t".to_owned()),
+            "quote must not be empty",
+        ),
+        (
+            payload(&"a".repeat(256), "Ho ho ho".to_owned()),
+            "author must be at most 255 characters",
+        ),
+        (
+            payload("Santa", "q".repeat(4097)),
+            "quote must be at most 4096 characters",
+        ),
+    ];
+
+    for (payload, message) in cases {
+        let res = draft(State(pool.clone()), HeaderMap::new(), AppJson(payload)).await;
+        assert_eq!(res.err(), Some(AppError::BadRequest(message.to_owned())));
+    }
+
+    // Limits count characters, not bytes
+    assert_eq!(
+        payload(&"ā".repeat(255), "🎄".repeat(4096)).validate(),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_cursor_round_trip() {
+    let created_at = DateTime::parse_from_rfc3339("2024-12-19T10:20:30.123456Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let id = Uuid::new_v4();
+
+    let token = encode_cursor(created_at, id, 5);
+
+    assert_eq!(decode_cursor(&token), Some(((created_at, id), 5)));
+    assert_eq!(decode_cursor("not a cursor"), None);
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_list_walks_every_quote_once() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let mut rows = Vec::new();
+    for i in 0..10 {
+        // Pairs share a timestamp, so pages also have to break ties on the id
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO quotes (id, author, quote, created_at) VALUES ($1, 'Santa', 'Ho', $2)",
+        )
+        .bind(id)
+        .bind(DateTime::from_timestamp(i / 2, 0))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        rows.push((i / 2, id));
+    }
+
+    let pages = list_pages(&db.pool, Some(3), 4).await;
+
+    db.remove().await;
+    assert_eq!(pages[3].next_token, None);
+    let listed = pages
+        .iter()
+        .flat_map(|page| page.quotes.iter().map(|quote| quote.id))
+        .collect::<Vec<_>>();
+    // Both sides order uuids by their bytes
+    rows.sort();
+    assert_eq!(
+        listed,
+        rows.into_iter().map(|(_, id)| id).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_csv_field_escaping() {
+    assert_eq!(csv_field("plain"), "plain");
+    assert_eq!(csv_field("a, b"), "\"a, b\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+}
+
+#[test]
+fn test_list_links() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(list_links(&headers, Some("abc")), None);
+
+    headers.insert(HOST, HeaderValue::from_static("example.com"));
+    headers.insert("X-Forwarded-Proto", HeaderValue::from_static("https"));
+    assert_eq!(
+        list_links(&headers, None).unwrap(),
+        "<https://example.com/19/list>; rel=\"first\""
+    );
+    assert_eq!(
+        list_links(&headers, Some("abc")).unwrap(),
+        "<https://example.com/19/list>; rel=\"first\", <https://example.com/19/list?token=abc>; rel=\"next\""
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_list_totals() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    for i in 0..7 {
+        sqlx::query(
+            "INSERT INTO quotes (id, author, quote, created_at) VALUES ($1, 'Santa', 'Ho', $2)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(DateTime::from_timestamp(i, 0))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    }
+
+    let pages = list_pages(&db.pool, None, 3).await;
+
+    db.remove().await;
+    for (i, page) in pages.iter().enumerate() {
+        assert_eq!(page.page, i as i64 + 1);
+        assert_eq!(page.total, 7);
+        assert_eq!(page.total_pages, 3);
+    }
+    assert!(pages[0].next_token.is_some());
+    assert!(pages[1].next_token.is_some());
+    assert_eq!(pages[2].next_token, None);
+    assert_eq!(pages[2].quotes.len(), 1);
+}
+
+#[test]
+fn test_page_count() {
+    assert_eq!(page_count(0, 3), 0);
+    assert_eq!(page_count(3, 3), 1);
+    assert_eq!(page_count(7, 3), 3);
+    assert_eq!(page_count(7, 5), 2);
+}
+
+/// Follows `next_token` from the first page of `/19/list` for `pages` pages. Only the first
+/// request sets `page_size`, so the rest rely on the token carrying it.
+#[cfg(test)]
+async fn list_pages(pool: &PgPool, page_size: Option<u32>, pages: usize) -> Vec<Quotes> {
+    let mut query = ListQuery {
+        token: None,
+        page_size,
+    };
+    let mut listed = Vec::new();
+    for _ in 0..pages {
+        let (_, Json(page)) = list(State(pool.clone()), HeaderMap::new(), AppQuery(query))
+            .await
+            .unwrap();
+        query = ListQuery {
+            token: page.next_token.clone(),
+            page_size: None,
+        };
+        listed.push(page);
+    }
+    listed
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_list_page_size() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    for i in 0..7 {
+        sqlx::query(
+            "INSERT INTO quotes (id, author, quote, created_at) VALUES ($1, 'Santa', 'Ho', $2)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(DateTime::from_timestamp(i, 0))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    }
+
+    let fives = list_pages(&db.pool, Some(5), 2).await;
+    let ones = list_pages(&db.pool, Some(1), 7).await;
+
+    db.remove().await;
+    assert_eq!(
+        fives
+            .iter()
+            .map(|page| page.quotes.len())
+            .collect::<Vec<_>>(),
+        [5, 2]
+    );
+    assert_eq!(fives[1].page, 2);
+    assert_eq!(fives[1].total_pages, 2);
+    assert_eq!(fives[1].next_token, None);
+    assert!(ones
+        .iter()
+        .all(|page| page.quotes.len() == 1 && page.total_pages == 7));
+    assert_eq!(ones[6].page, 7);
+    assert_eq!(ones[6].next_token, None);
+}
+
+#[tokio::test]
+async fn test_list_page_size_out_of_range() {
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+
+    for page_size in [0, 101] {
+        let query = ListQuery {
+            token: None,
+            page_size: Some(page_size),
+        };
+        let res = list(State(pool.clone()), HeaderMap::new(), AppQuery(query)).await;
+        assert_eq!(
+            res.err(),
+            Some(AppError::BadRequest(
+                "page_size must be between 1 and 100".to_owned()
+            ))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_query_failures_keep_the_error_private() {
+    let query = || ListQuery {
+        token: None,
+        page_size: None,
+    };
+
+    let trash = trash(State(unreachable_pool()), AppQuery(query())).await;
+    let list = list(
+        State(unreachable_pool()),
+        HeaderMap::new(),
+        AppQuery(query()),
+    )
+    .await;
+
+    assert_eq!(trash.err(), Some(AppError::ServiceUnavailable));
+    // `/19/list` keeps the 400 it always had, just without the driver's message
+    assert_eq!(
+        list.err(),
+        Some(AppError::BadRequest("Query failed".to_owned()))
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_draft_idempotency_key() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert(&IDEMPOTENCY_KEY, HeaderValue::from_static("letter-42"));
+    let draft_once = || async {
+        let payload = Payload {
+            author: "Santa".to_owned(),
+            quote: "Ho ho ho".to_owned(),
+        };
+        let (status, Json(quote)) =
+            draft(State(db.pool.clone()), headers.clone(), AppJson(payload))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        quote.id
+    };
+
+    let first = draft_once().await;
+    let replayed = draft_once().await;
+    sqlx::query("UPDATE idempotency_cache SET created_at = now() - INTERVAL '25 hours'")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let expired = draft_once().await;
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM quotes")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+
+    db.remove().await;
+    assert_eq!(replayed, first);
+    assert_ne!(expired, first);
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_undo_if_match() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO quotes (id, author, quote) VALUES ($1, 'Santa', 'Ho')")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let undo_at = |id: Uuid, version: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, HeaderValue::from_static(version));
+        let payload = Payload {
+            author: "Santa".to_owned(),
+            quote: "Ho ho".to_owned(),
+        };
+        undo(
+            State(db.pool.clone()),
+            AppPath(id.to_string()),
+            headers,
+            AppJson(payload),
+        )
+    };
+
+    let updated = undo_at(id, "1").await.map(|Json(quote)| quote.version);
+    let stale = undo_at(id, "\"1\"").await.map(|Json(quote)| quote.version);
+    let missing_id = Uuid::new_v4();
+    let missing = undo_at(missing_id, "1")
+        .await
+        .map(|Json(quote)| quote.version);
+
+    db.remove().await;
+    assert_eq!(updated, Ok(2));
+    assert_eq!(stale, Err(AppError::VersionConflict(2)));
+    assert_eq!(missing, Err(QuoteError(missing_id).into()));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_quote_length_constraints() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO quotes (id, author, quote) VALUES ($1, 'Santa', 'Ho')")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let long_author = "a".repeat(MAX_AUTHOR_CHARS + 1);
+
+    let inserted = sqlx::query("INSERT INTO quotes (id, author, quote) VALUES ($1, $2, 'Ho')")
+        .bind(Uuid::new_v4())
+        .bind(&long_author)
+        .execute(&db.pool)
+        .await
+        .map(|_| ())
+        .map_err(write_failed);
+    let payload = Payload {
+        author: long_author,
+        quote: "Ho ho".to_owned(),
+    };
+    let undone = undo(
+        State(db.pool.clone()),
+        AppPath(id.to_string()),
+        HeaderMap::new(),
+        AppJson(payload),
+    )
+    .await
+    .map(|Json(quote)| quote.version);
+
+    db.remove().await;
+    let violation = AppError::ConstraintViolation("quotes_author_length".to_owned());
+    assert_eq!(inserted, Err(violation));
+    let res = undone.unwrap_err().into_response();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let errors = &error_document(res).await["errors"];
+    assert_eq!(errors[0]["detail"], "constraint_violation");
+    assert_eq!(errors[0]["meta"]["constraint"], "quotes_author_length");
+}
+
+#[test]
+fn test_quote_identity() {
+    let quote = |id, author: &str, version| Quote {
+        id,
+        author: author.to_owned(),
+        quote: "Ho ho ho".to_owned(),
+        created_at: Utc::now(),
+        version,
+        deleted_at: None,
+    };
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+    assert!(quote(a, "Santa", 1) == quote(a, "Rudolph", 2));
+    assert!(quote(a, "Santa", 1) != quote(b, "Santa", 1));
+
+    let unique: std::collections::HashSet<_> = [
+        quote(a, "Santa", 1),
+        quote(b, "Santa", 1),
+        quote(a, "Rudolph", 2),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(unique.len(), 2);
+    assert_eq!(unique.get(&quote(a, "", 0)).unwrap().author, "Santa");
+}
+
+#[tokio::test]
+async fn test_amend_needs_a_field() {
+    let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+    let amend_with = |author: Option<&str>, quote: Option<&str>| {
+        let payload = PatchPayload {
+            author: author.map(str::to_owned),
+            quote: quote.map(str::to_owned),
+        };
+        amend(
+            State(pool.clone()),
+            AppPath(Uuid::nil().to_string()),
+            AppJson(payload),
+        )
+    };
+
+    let cases = [
+        (None, None, "author or quote is required"),
+        (Some(" "), None, "author must not be empty"),
+        (None, Some(""), "quote must not be empty"),
+    ];
+    for (author, quote, message) in cases {
+        assert_eq!(
+            amend_with(author, quote).await.err(),
+            Some(AppError::BadRequest(message.to_owned()))
+        );
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_amend() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO quotes (id, author, quote) VALUES ($1, 'Santa', 'Ho')")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let amend_with = |id: Uuid, author: Option<&str>, quote: Option<&str>| {
+        let payload = PatchPayload {
+            author: author.map(str::to_owned),
+            quote: quote.map(str::to_owned),
+        };
+        let pool = db.pool.clone();
+        async move {
+            amend(State(pool), AppPath(id.to_string()), AppJson(payload))
+                .await
+                .map(|Json(quote)| (quote.author, quote.quote, quote.version))
+        }
+    };
+    let quote =
+        |author: &str, quote: &str, version| Ok((author.to_owned(), quote.to_owned(), version));
+
+    let author_only = amend_with(id, Some("Rudolph"), None).await;
+    let quote_only = amend_with(id, None, Some("Ho ho")).await;
+    let both = amend_with(id, Some("Mrs Claus"), Some("Ho ho ho")).await;
+    let neither = amend_with(id, None, None).await;
+    let missing_id = Uuid::new_v4();
+    let missing = amend_with(missing_id, Some("Santa"), None).await;
+
+    db.remove().await;
+    assert_eq!(author_only, quote("Rudolph", "Ho", 2));
+    assert_eq!(quote_only, quote("Rudolph", "Ho ho", 3));
+    assert_eq!(both, quote("Mrs Claus", "Ho ho ho", 4));
+    assert_eq!(
+        neither,
+        Err(AppError::BadRequest(
+            "author or quote is required".to_owned()
+        ))
+    );
+    assert_eq!(missing, Err(QuoteError(missing_id).into()));
+}
+
+#[tokio::test]
+async fn test_version_conflict_document() {
+    let res = AppError::VersionConflict(3).into_response();
+
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let errors = &error_document(res).await["errors"];
+    assert_eq!(errors[0]["detail"], "version_conflict");
+    assert_eq!(errors[0]["meta"]["current_version"], 3);
+}
+
+#[test]
+fn test_author_cursor_round_trip() {
+    for author in ["Santa", "Mrs. Claus / North Pole", ""] {
+        let token = encode_author_cursor(author, 20);
+        assert_eq!(decode_author_cursor(&token), Some((author.to_owned(), 20)));
+    }
+    assert_eq!(decode_author_cursor("not base64!"), None);
+    assert_eq!(decode_author_cursor(&URL_SAFE_NO_PAD.encode("Santa")), None);
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_authors() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    for (i, author) in ["Santa", "Rudolph", "Santa", "Santa", "Rudolph"]
+        .into_iter()
+        .enumerate()
+    {
+        sqlx::query("INSERT INTO quotes (id, author, quote, created_at) VALUES ($1, $2, 'Ho', $3)")
+            .bind(Uuid::new_v4())
+            .bind(author)
+            .bind(DateTime::from_timestamp(i as i64, 0))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("UPDATE quotes SET deleted_at = now() WHERE created_at = $1")
+        .bind(DateTime::from_timestamp(4, 0))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let authors_page = |token, page_size| {
+        authors(
+            State(db.pool.clone()),
+            AppQuery(AuthorsQuery { token, page_size }),
+        )
+    };
+
+    let Json(all) = authors_page(None, None).await.unwrap();
+    let Json(first) = authors_page(None, Some(1)).await.unwrap();
+    let Json(second) = authors_page(first.next_token.clone(), None).await.unwrap();
+    let Json(santa) = author_quotes(
+        State(db.pool.clone()),
+        AppPath("Santa".to_owned()),
+        AppQuery(ListQuery {
+            token: None,
+            page_size: None,
+        }),
+    )
+    .await
+    .unwrap();
+
+    db.remove().await;
+    let count = |author: &str, quote_count| AuthorCount {
+        author: author.to_owned(),
+        quote_count,
+    };
+    assert_eq!(all.authors, [count("Rudolph", 1), count("Santa", 3)]);
+    assert_eq!(all.next_token, None);
+    assert_eq!(first.authors, [count("Rudolph", 1)]);
+    assert_eq!(second.authors, [count("Santa", 3)]);
+    assert_eq!(second.next_token, None);
+    assert_eq!(santa.total, 3);
+    assert!(santa.quotes.iter().all(|quote| quote.author == "Santa"));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_remove_and_restore() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let ids = insert_quotes(&db.pool, &[("Santa", "Ho"), ("Rudolph", "Glow")]).await;
+    let path = || AppPath(ids[0].to_string());
+    let listed = || async {
+        let query = ListQuery {
+            token: None,
+            page_size: None,
+        };
+        let (_, Json(page)) = list(State(db.pool.clone()), HeaderMap::new(), AppQuery(query))
+            .await
+            .unwrap();
+        page.quotes
+            .into_iter()
+            .map(|quote| quote.id)
+            .collect::<Vec<_>>()
+    };
+
+    let not_deleted = restore(State(db.pool.clone()), path()).await.err();
+    let removed = remove(State(db.pool.clone()), path())
+        .await
+        .map(|Json(quote)| quote.id);
+    let cited_removed = cite(State(db.pool.clone()), path()).await.err();
+    let listed_removed = listed().await;
+    let restored = restore(State(db.pool.clone()), path())
+        .await
+        .map(|Json(quote)| quote.id);
+    let cited_restored = cite(State(db.pool.clone()), path())
+        .await
+        .map(|Json(quote)| quote.id);
+    let listed_restored = listed().await;
+
+    db.remove().await;
+    let missing = || Some(AppError::from(QuoteError(ids[0])));
+    assert_eq!(not_deleted, missing());
+    assert_eq!(removed, Ok(ids[0]));
+    assert_eq!(cited_removed, missing());
+    assert_eq!(listed_removed, [ids[1]]);
+    assert_eq!(restored, Ok(ids[0]));
+    assert_eq!(cited_restored, Ok(ids[0]));
+    assert_eq!(listed_restored, ids);
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_history_after_two_updates() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let ids = insert_quotes(&db.pool, &[("Santa", "Ho")]).await;
+    let path = || AppPath(ids[0].to_string());
+    let mut versions = Vec::new();
+    for (author, quote) in [("Santa", "Ho ho"), ("Mrs. Claus", "Ho ho ho")] {
+        let payload = Payload {
+            author: author.to_owned(),
+            quote: quote.to_owned(),
+        };
+        let Json(updated) = undo(
+            State(db.pool.clone()),
+            path(),
+            HeaderMap::new(),
+            AppJson(payload),
+        )
+        .await
+        .unwrap();
+        versions.push(updated.version);
+    }
+
+    let Json(recorded) = history(State(db.pool.clone()), path()).await.unwrap();
+    let second = history_version(State(db.pool.clone()), AppPath((ids[0].to_string(), 2)))
+        .await
+        .map(|Json(entry)| entry.quote);
+    let fourth = history_version(State(db.pool.clone()), AppPath((ids[0].to_string(), 4)))
+        .await
+        .err();
+    let unknown = history(State(db.pool.clone()), AppPath(Uuid::new_v4().to_string()))
+        .await
+        .err();
+
+    db.remove().await;
+    assert_eq!(versions, [2, 3]);
+    assert_eq!(recorded.id, ids[0]);
+    assert_eq!(
+        recorded
+            .history
+            .iter()
+            .map(|entry| (entry.version, entry.author.as_str(), entry.quote.as_str()))
+            .collect::<Vec<_>>(),
+        [
+            (1, "Santa", "Ho"),
+            (2, "Santa", "Ho ho"),
+            (3, "Mrs. Claus", "Ho ho ho")
+        ]
+    );
+    assert_eq!(second, Ok("Ho ho".to_owned()));
+    assert_eq!(fourth, Some(AppError::NotFound));
+    assert_eq!(unknown, Some(AppError::NotFound));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_random() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let ids = insert_quotes(
+        &db.pool,
+        &[("Santa", "Ho"), ("Rudolph", "Glow"), ("Santa", "Sleigh")],
+    )
+    .await;
+    let pick = |author: Option<&str>| {
+        let query = RandomQuery {
+            author: author.map(str::to_owned),
+        };
+        random(State(db.pool.clone()), AppQuery(query))
+    };
+
+    let mut any = Vec::new();
+    for _ in 0..10 {
+        any.push(pick(None).await.unwrap().0.id);
+    }
+    let Json(rudolph) = pick(Some("rudolph")).await.unwrap();
+    let unknown = pick(Some("Grinch")).await.err();
+
+    db.remove().await;
+    assert!(any.iter().all(|id| ids.contains(id)));
+    assert_eq!((rudolph.id, rudolph.author.as_str()), (ids[1], "Rudolph"));
+    assert_eq!(unknown, Some(AppError::NotFound));
+}
+
+/// Splits RFC 4180 text into records of unquoted fields, the inverse of [`csv_field`].
+#[cfg(test)]
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let (mut records, mut record, mut field) = (Vec::new(), Vec::new(), String::new());
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (_, '"') => quoted = !quoted,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {
+                chars.next();
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    records
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_csv_export_round_trip() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    let quotes = (0..50)
+        .map(|i| match i % 3 {
+            0 => format!("Ho {i}"),
+            1 => format!("Ho, ho \"{i}\""),
+            _ => format!("Ho\r\nho {i}"),
+        })
+        .collect::<Vec<_>>();
+    let rows = quotes
+        .iter()
+        .map(|quote| ("Santa", quote.as_str()))
+        .collect::<Vec<_>>();
+    let ids = insert_quotes(&db.pool, &rows).await;
+
+    let res = export(
+        State(db.pool.clone()),
+        AppQuery(ExportQuery {
+            format: "csv".to_owned(),
+        }),
+    )
+    .await;
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    db.remove().await;
+    let records = parse_csv(std::str::from_utf8(&body).unwrap());
+    assert_eq!(
+        records[0],
+        ["id", "author", "quote", "created_at", "version"]
+    );
+    let exported = &records[1..];
+    assert_eq!(
+        exported
+            .iter()
+            .map(|record| Uuid::from_str(&record[0]).unwrap())
+            .collect::<Vec<_>>(),
+        ids
+    );
+    assert!(exported
+        .iter()
+        .zip(&quotes)
+        .all(|(record, quote)| record.len() == 5 && record[2] == *quote));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_list_link_header() {
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    insert_quotes(&db.pool, &[("Santa", "Ho"); 4]).await;
+    let mut headers = HeaderMap::new();
+    headers.insert(HOST, HeaderValue::from_static("example.com"));
+    let list_with = |page_size| {
+        list(
+            State(db.pool.clone()),
+            headers.clone(),
+            AppQuery(ListQuery {
+                token: None,
+                page_size,
+            }),
+        )
+    };
+
+    let (multi_headers, Json(multi)) = list_with(None).await.unwrap();
+    let (single_headers, Json(single)) = list_with(Some(10)).await.unwrap();
+
+    db.remove().await;
+    let next = format!(
+        "<http://example.com/19/list?token={}>; rel=\"next\"",
+        multi.next_token.unwrap()
+    );
+    let multi_link = multi_headers[LINK].to_str().unwrap();
+    assert!(multi_link.starts_with("<http://example.com/19/list>; rel=\"first\""));
+    assert!(multi_link.ends_with(&next), "{multi_link}");
+    assert_eq!(single.next_token, None);
+    assert_eq!(
+        single_headers[LINK],
+        "<http://example.com/19/list>; rel=\"first\""
+    );
+}
@@ -1,103 +1,1067 @@
 use std::{
-    net::{Ipv4Addr, Ipv6Addr},
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     ops::BitXor,
 };
 
-use axum::extract::Query;
-use itertools::Itertools;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use utoipa::{IntoParams, ToSchema};
+
+/// Rejection naming the first query field that isn't an IPv4 address.
+type Ipv4Rejection = (StatusCode, String);
+
+/// Reads `name`, or its capitalised alias (`From` for `from`) that some clients send.
+fn ipv4_field(
+    query: &HashMap<String, String>,
+    name: &'static str,
+) -> Result<Ipv4Addr, Ipv4Rejection> {
+    let mut alias = name.to_owned();
+    alias[..1].make_ascii_uppercase();
+    let Some(value) = query.get(name).or_else(|| query.get(&alias)) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Missing query parameter: {name}\n"),
+        ));
+    };
+    value.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid IPv4 address: {name}\n"),
+        )
+    })
+}
+
+fn ipv4_query(parts: &Parts) -> Result<HashMap<String, String>, Ipv4Rejection> {
+    Query::try_from_uri(&parts.uri)
+        .map(|Query(query)| query)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid query string\n".to_owned()))
+}
 
-#[derive(serde::Deserialize)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct DestQParams {
+    #[param(value_type = String, format = Ipv4)]
     from: Ipv4Addr,
+    #[param(value_type = String, format = Ipv4)]
     key: Ipv4Addr,
 }
 
-pub async fn dest(params: Query<DestQParams>) -> String {
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for DestQParams {
+    type Rejection = Ipv4Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = ipv4_query(parts)?;
+        Ok(Self {
+            from: ipv4_field(&query, "from")?,
+            key: ipv4_field(&query, "key")?,
+        })
+    }
+}
+
+fn v4_dest(from: Ipv4Addr, key: Ipv4Addr) -> Ipv4Addr {
     let octets = [
-        params.from.octets()[0]
-            .overflowing_add(params.key.octets()[0])
-            .0,
-        params.from.octets()[1]
-            .overflowing_add(params.key.octets()[1])
-            .0,
-        params.from.octets()[2]
-            .overflowing_add(params.key.octets()[2])
-            .0,
-        params.from.octets()[3]
-            .overflowing_add(params.key.octets()[3])
-            .0,
+        from.octets()[0].overflowing_add(key.octets()[0]).0,
+        from.octets()[1].overflowing_add(key.octets()[1]).0,
+        from.octets()[2].overflowing_add(key.octets()[2]).0,
+        from.octets()[3].overflowing_add(key.octets()[3]).0,
     ];
-    let destination = Ipv4Addr::from(octets);
+    Ipv4Addr::from(octets)
+}
+
+#[utoipa::path(get, path = "/2/dest", params(DestQParams), responses((status = 200, description = "Destination address", body = String), (status = 400, description = "Missing or malformed address, named in the body")))]
+pub async fn dest(params: DestQParams) -> String {
+    let destination = v4_dest(params.from, params.key);
 
     destination.to_string()
 }
 
-#[derive(serde::Deserialize)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct KeyQParams {
+    #[param(value_type = String, format = Ipv4)]
     from: Ipv4Addr,
+    #[param(value_type = String, format = Ipv4)]
     to: Ipv4Addr,
 }
-pub async fn key(params: Query<KeyQParams>) -> String {
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for KeyQParams {
+    type Rejection = Ipv4Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = ipv4_query(parts)?;
+        Ok(Self {
+            from: ipv4_field(&query, "from")?,
+            to: ipv4_field(&query, "to")?,
+        })
+    }
+}
+
+fn v4_key(from: Ipv4Addr, to: Ipv4Addr) -> Ipv4Addr {
     let octets = [
-        params.to.octets()[0]
-            .overflowing_sub(params.from.octets()[0])
-            .0,
-        params.to.octets()[1]
-            .overflowing_sub(params.from.octets()[1])
-            .0,
-        params.to.octets()[2]
-            .overflowing_sub(params.from.octets()[2])
-            .0,
-        params.to.octets()[3]
-            .overflowing_sub(params.from.octets()[3])
-            .0,
+        to.octets()[0].overflowing_sub(from.octets()[0]).0,
+        to.octets()[1].overflowing_sub(from.octets()[1]).0,
+        to.octets()[2].overflowing_sub(from.octets()[2]).0,
+        to.octets()[3].overflowing_sub(from.octets()[3]).0,
     ];
-    let destination = Ipv4Addr::from(octets);
+    Ipv4Addr::from(octets)
+}
+
+#[utoipa::path(get, path = "/2/key", params(KeyQParams), responses((status = 200, description = "Key that maps `from` to `to`", body = String), (status = 400, description = "Missing or malformed address, named in the body")))]
+pub async fn key(params: KeyQParams) -> String {
+    let destination = v4_key(params.from, params.to);
 
     destination.to_string()
 }
 
-#[derive(serde::Deserialize)]
+const MAX_BATCH_SIZE: usize = 256;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct BatchDestEntry {
+    from: Option<String>,
+    key: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct BatchDest {
+    #[schema(value_type = Option<String>, format = Ipv4)]
+    dest: Option<Ipv4Addr>,
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct BatchKeyEntry {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct BatchKey {
+    #[schema(value_type = Option<String>, format = Ipv4)]
+    key: Option<Ipv4Addr>,
+}
+
+fn parse_v4(field: Option<&str>) -> Option<Ipv4Addr> {
+    field?.parse().ok()
+}
+
+#[utoipa::path(post, path = "/2/batch/dest", request_body = Vec<BatchDestEntry>, responses((status = 200, body = Vec<BatchDest>), (status = 413, description = "More than 256 entries")))]
+pub async fn batch_dest(
+    Json(entries): Json<Vec<BatchDestEntry>>,
+) -> Result<Json<Vec<BatchDest>>, StatusCode> {
+    if entries.len() > MAX_BATCH_SIZE {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let dests = entries
+        .iter()
+        .map(|entry| BatchDest {
+            dest: parse_v4(entry.from.as_deref())
+                .zip(parse_v4(entry.key.as_deref()))
+                .map(|(from, key)| v4_dest(from, key)),
+        })
+        .collect();
+
+    Ok(Json(dests))
+}
+
+#[utoipa::path(post, path = "/2/batch/key", request_body = Vec<BatchKeyEntry>, responses((status = 200, body = Vec<BatchKey>), (status = 413, description = "More than 256 entries")))]
+pub async fn batch_key(
+    Json(entries): Json<Vec<BatchKeyEntry>>,
+) -> Result<Json<Vec<BatchKey>>, StatusCode> {
+    if entries.len() > MAX_BATCH_SIZE {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let keys = entries
+        .iter()
+        .map(|entry| BatchKey {
+            key: parse_v4(entry.from.as_deref())
+                .zip(parse_v4(entry.to.as_deref()))
+                .map(|(from, to)| v4_key(from, to)),
+        })
+        .collect();
+
+    Ok(Json(keys))
+}
+
+/// `Ipv6Addr`'s `Display` writes IPv4-mapped addresses as `::ffff:a.b.c.d`; these handlers
+/// deal in IPv6 only, so keep the hex form for those too.
+fn v6_to_string(ip: Ipv6Addr) -> String {
+    match ip.to_ipv4_mapped() {
+        Some(_) => {
+            let [.., high, low] = ip.segments();
+            format!("::ffff:{high:x}:{low:x}")
+        }
+        None => ip.to_string(),
+    }
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct V6DestQParams {
+    #[serde(alias = "From")]
+    #[param(value_type = String, format = Ipv6)]
     from: Ipv6Addr,
+    #[serde(alias = "Key")]
+    #[param(value_type = String, format = Ipv6)]
     key: Ipv6Addr,
 }
 
+#[utoipa::path(get, path = "/2/v6/dest", params(V6DestQParams), responses((status = 200, description = "Destination address", body = String)))]
 pub async fn v6_dest(params: Query<V6DestQParams>) -> String {
+    // Both sides are `[u8; 16]`, so a plain `zip` can't drop octets; `zip_eq` only added a
+    // length check that could never fail, and a panic if a refactor ever broke that
+    let (from, key) = (params.from.octets(), params.key.octets());
+    debug_assert_eq!(from.len(), key.len());
     let mut segments = [0; 16];
-    for (idx, (from, key)) in params
-        .from
-        .octets()
-        .into_iter()
-        .zip_eq(params.key.octets())
-        .enumerate()
-    {
+    for (idx, (from, key)) in from.into_iter().zip(key).enumerate() {
         let value = from.bitxor(key);
         segments[idx] = value;
     }
     let destination = Ipv6Addr::from(segments);
 
-    destination.to_string()
+    v6_to_string(destination)
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct V6KeyQParams {
+    #[serde(alias = "From")]
+    #[param(value_type = String, format = Ipv6)]
     from: Ipv6Addr,
+    #[serde(alias = "To")]
+    #[param(value_type = String, format = Ipv6)]
     to: Ipv6Addr,
 }
+#[utoipa::path(get, path = "/2/v6/key", params(V6KeyQParams), responses((status = 200, description = "Key that maps `from` to `to`", body = String)))]
 pub async fn v6_key(params: Query<V6KeyQParams>) -> String {
+    // See `v6_dest` for why `zip` is enough here
+    let (to, from) = (params.to.octets(), params.from.octets());
+    debug_assert_eq!(to.len(), from.len());
     let mut segments = [0; 16];
-    for (idx, (to, from)) in params
-        .to
-        .octets()
-        .into_iter()
-        .zip_eq(params.from.octets())
-        .enumerate()
-    {
+    for (idx, (to, from)) in to.into_iter().zip(from).enumerate() {
         let value = to.bitxor(from);
         segments[idx] = value;
     }
     let destination = Ipv6Addr::from(segments);
 
-    destination.to_string()
+    v6_to_string(destination)
+}
+
+#[utoipa::path(get, path = "/2/v6/add/dest", params(V6DestQParams), responses((status = 200, description = "Destination address", body = String)))]
+pub async fn v6_add_dest(params: Query<V6DestQParams>) -> String {
+    let from = u128::from_be_bytes(params.from.octets());
+    let key = u128::from_be_bytes(params.key.octets());
+    let destination = Ipv6Addr::from(from.wrapping_add(key));
+
+    v6_to_string(destination)
+}
+
+#[utoipa::path(get, path = "/2/v6/add/key", params(V6KeyQParams), responses((status = 200, description = "Key that maps `from` to `to`", body = String)))]
+pub async fn v6_add_key(params: Query<V6KeyQParams>) -> String {
+    let from = u128::from_be_bytes(params.from.octets());
+    let to = u128::from_be_bytes(params.to.octets());
+    let key = Ipv6Addr::from(to.wrapping_sub(from));
+
+    v6_to_string(key)
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CidrQParams {
+    #[param(value_type = String, format = Ipv4)]
+    ip: Ipv4Addr,
+    prefix: u8,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct Cidr {
+    #[schema(value_type = String, format = Ipv4)]
+    network: Ipv4Addr,
+    #[schema(value_type = Option<String>, format = Ipv4)]
+    broadcast: Option<Ipv4Addr>,
+    #[schema(value_type = String, format = Ipv4)]
+    first_host: Ipv4Addr,
+    #[schema(value_type = String, format = Ipv4)]
+    last_host: Ipv4Addr,
+    hosts: u32,
+}
+
+fn netmask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+fn cidr_block(ip: Ipv4Addr, prefix: u8) -> Option<Cidr> {
+    if prefix > 32 {
+        return None;
+    }
+    let mask = netmask(prefix);
+    let network = u32::from(ip) & mask;
+    let broadcast = u32::from(ip) | !mask;
+
+    // RFC 3021: a /31 is a point-to-point link where both addresses are hosts, and a /32 is a
+    // single host; neither has a broadcast address
+    let block = match prefix {
+        32 => Cidr {
+            network: network.into(),
+            broadcast: None,
+            first_host: network.into(),
+            last_host: network.into(),
+            hosts: 1,
+        },
+        31 => Cidr {
+            network: network.into(),
+            broadcast: None,
+            first_host: network.into(),
+            last_host: broadcast.into(),
+            hosts: 2,
+        },
+        _ => Cidr {
+            network: network.into(),
+            broadcast: Some(broadcast.into()),
+            first_host: (network + 1).into(),
+            last_host: (broadcast - 1).into(),
+            hosts: broadcast - network - 1,
+        },
+    };
+    Some(block)
+}
+
+#[utoipa::path(get, path = "/2/cidr", params(CidrQParams), responses((status = 200, body = Cidr), (status = 400, description = "Prefix longer than 32")))]
+pub async fn cidr(params: Query<CidrQParams>) -> Result<Json<Cidr>, StatusCode> {
+    cidr_block(params.ip, params.prefix)
+        .map(Json)
+        .ok_or(StatusCode::BAD_REQUEST)
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct OverlapPayload {
+    a: String,
+    b: String,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct Overlap {
+    overlaps: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intersection: Option<String>,
+}
+
+/// Parses `a.b.c.d/len` into its network address (host bits cleared) and prefix length.
+fn parse_cidr(cidr: &str) -> Option<(u32, u8)> {
+    let (ip, prefix) = cidr.split_once('/')?;
+    let ip: Ipv4Addr = ip.parse().ok()?;
+    let prefix: u8 = prefix.parse().ok().filter(|prefix| *prefix <= 32)?;
+    Some((u32::from(ip) & netmask(prefix), prefix))
+}
+
+fn cidr_intersection(a: (u32, u8), b: (u32, u8)) -> Option<(u32, u8)> {
+    // CIDR blocks are either disjoint or nested, so the intersection is always the tighter
+    // block whenever both agree on the looser block's prefix
+    let (looser, tighter) = if a.1 <= b.1 { (a, b) } else { (b, a) };
+    (tighter.0 & netmask(looser.1) == looser.0).then_some(tighter)
+}
+
+#[utoipa::path(post, path = "/2/overlap", request_body = OverlapPayload, responses((status = 200, body = Overlap), (status = 400, description = "Malformed CIDR block")))]
+pub async fn overlap(Json(payload): Json<OverlapPayload>) -> Result<Json<Overlap>, StatusCode> {
+    let a = parse_cidr(&payload.a).ok_or(StatusCode::BAD_REQUEST)?;
+    let b = parse_cidr(&payload.b).ok_or(StatusCode::BAD_REQUEST)?;
+
+    let intersection = cidr_intersection(a, b)
+        .map(|(network, prefix)| format!("{}/{prefix}", Ipv4Addr::from(network)));
+    Ok(Json(Overlap {
+        overlaps: intersection.is_some(),
+        intersection,
+    }))
+}
+
+/// Accepts `true`/`false` as well as `1`/`0` for boolean query flags.
+fn query_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let flag = <std::borrow::Cow<str> as serde::Deserialize>::deserialize(deserializer)?;
+    match &*flag {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        other => Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Str(other),
+            &"a boolean flag",
+        )),
+    }
+}
+
+fn parse_ip(ip: &str, v6: bool) -> Result<IpAddr, StatusCode> {
+    if v6 {
+        ip.parse::<Ipv6Addr>().map(IpAddr::V6)
+    } else {
+        ip.parse::<Ipv4Addr>().map(IpAddr::V4)
+    }
+    .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClassifyQParams {
+    ip: String,
+    #[serde(default, deserialize_with = "query_flag")]
+    v6: bool,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct Classification {
+    #[schema(value_type = String)]
+    ip: IpAddr,
+    class: Vec<&'static str>,
+}
+
+fn classify_ip(ip: IpAddr) -> Vec<&'static str> {
+    let mut classes = Vec::new();
+    match ip {
+        IpAddr::V4(ip) => {
+            if ip.is_private() {
+                classes.push("private");
+            }
+            if ip.is_loopback() {
+                classes.push("loopback");
+            }
+            if ip.is_multicast() {
+                classes.push("multicast");
+            }
+            if ip.is_link_local() {
+                classes.push("link_local");
+            }
+            if ip == Ipv4Addr::BROADCAST {
+                classes.push("broadcast");
+            }
+        }
+        IpAddr::V6(ip) => {
+            // fc00::/7 unique local addresses
+            if ip.segments()[0] & 0xfe00 == 0xfc00 {
+                classes.push("private");
+            }
+            if ip.is_loopback() {
+                classes.push("loopback");
+            }
+            if ip.is_multicast() {
+                classes.push("multicast");
+            }
+            // fe80::/10 link-local unicast
+            if ip.segments()[0] & 0xffc0 == 0xfe80 {
+                classes.push("link_local");
+            }
+        }
+    }
+    if classes.is_empty() {
+        classes.push("public");
+    }
+    classes
+}
+
+#[utoipa::path(get, path = "/2/classify", params(ClassifyQParams), responses((status = 200, body = Classification), (status = 400, description = "Malformed address")))]
+pub async fn classify(params: Query<ClassifyQParams>) -> Result<Json<Classification>, StatusCode> {
+    let ip = parse_ip(&params.ip, params.v6)?;
+
+    Ok(Json(Classification {
+        ip,
+        class: classify_ip(ip),
+    }))
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Ip2IntQParams {
+    #[param(value_type = String, format = Ipv4)]
+    ip: Ipv4Addr,
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Int2IpQParams {
+    value: u32,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct IpValue {
+    value: u32,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct IpAddress {
+    #[schema(value_type = String)]
+    ip: IpAddr,
+}
+
+/// Malformed addresses and integers outside of `u32` are rejected by the `Query` extractor
+/// with `400 Bad Request`.
+#[utoipa::path(get, path = "/2/ip2int", params(Ip2IntQParams), responses((status = 200, body = IpValue), (status = 400, description = "Malformed address")))]
+pub async fn ip2int(params: Query<Ip2IntQParams>) -> Json<IpValue> {
+    Json(IpValue {
+        value: u32::from_be_bytes(params.ip.octets()),
+    })
+}
+
+#[utoipa::path(get, path = "/2/int2ip", params(Int2IpQParams), responses((status = 200, body = IpAddress), (status = 400, description = "Value outside of `u32`")))]
+pub async fn int2ip(params: Query<Int2IpQParams>) -> Json<IpAddress> {
+    Json(IpAddress {
+        ip: Ipv4Addr::from(params.value).into(),
+    })
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StepQParams {
+    ip: String,
+    #[serde(default, deserialize_with = "query_flag")]
+    v6: bool,
+}
+
+fn step_ip(ip: IpAddr, forward: bool) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let ip = u32::from(ip);
+            let ip = if forward {
+                ip.wrapping_add(1)
+            } else {
+                ip.wrapping_sub(1)
+            };
+            IpAddr::V4(ip.into())
+        }
+        IpAddr::V6(ip) => {
+            let ip = u128::from(ip);
+            let ip = if forward {
+                ip.wrapping_add(1)
+            } else {
+                ip.wrapping_sub(1)
+            };
+            IpAddr::V6(ip.into())
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/2/next", params(StepQParams), responses((status = 200, body = IpAddress), (status = 400, description = "Malformed address")))]
+pub async fn next_ip(params: Query<StepQParams>) -> Result<Json<IpAddress>, StatusCode> {
+    let ip = parse_ip(&params.ip, params.v6)?;
+    Ok(Json(IpAddress {
+        ip: step_ip(ip, true),
+    }))
+}
+
+#[utoipa::path(get, path = "/2/prev", params(StepQParams), responses((status = 200, body = IpAddress), (status = 400, description = "Malformed address")))]
+pub async fn prev_ip(params: Query<StepQParams>) -> Result<Json<IpAddress>, StatusCode> {
+    let ip = parse_ip(&params.ip, params.v6)?;
+    Ok(Json(IpAddress {
+        ip: step_ip(ip, false),
+    }))
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MulticastQParams {
+    ip: String,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, ToSchema)]
+pub struct MulticastValidity {
+    valid: bool,
+    scope: &'static str,
+    reserved: bool,
+}
+
+fn multicast_validity(ip: IpAddr) -> MulticastValidity {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, third, _] = ip.octets();
+            // RFC 5771: 224.0.0.0/24 is the Local Network Control Block, reserved for
+            // routing protocols and never forwarded
+            let reserved = first == 224 && second == 0 && third == 0;
+            // RFC 2365: 239.0.0.0/8 is administratively scoped
+            let scope = match first {
+                224 if reserved => "link_local",
+                239 => "site_local",
+                224..=238 => "global",
+                _ => "unknown",
+            };
+            MulticastValidity {
+                valid: ip.is_multicast() && !reserved,
+                scope,
+                reserved,
+            }
+        }
+        IpAddr::V6(ip) => {
+            // RFC 4291: the low nibble of the second octet is the scope, 0 and F are reserved
+            let scope_id = ip.octets()[1] & 0x0f;
+            let scope = match scope_id {
+                0x2 => "link_local",
+                0x5 => "site_local",
+                0xe => "global",
+                _ => "unknown",
+            };
+            MulticastValidity {
+                valid: ip.is_multicast() && scope != "unknown",
+                scope,
+                reserved: ip.is_multicast() && matches!(scope_id, 0x0 | 0xf),
+            }
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/2/multicast/valid", params(MulticastQParams), responses((status = 200, body = MulticastValidity), (status = 400, description = "Malformed address")))]
+pub async fn multicast_valid(
+    params: Query<MulticastQParams>,
+) -> Result<Json<MulticastValidity>, StatusCode> {
+    let ip: IpAddr = params.ip.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(Json(multicast_validity(ip)))
+}
+
+/// Percent-encodes every byte, so any input makes a valid query string.
+#[cfg(test)]
+fn v6_query_uri(path: &str, params: [(&str, &[u8]); 2]) -> axum::http::Uri {
+    use std::fmt::Write;
+
+    let mut uri = format!("{path}?");
+    for (idx, (name, value)) in params.iter().enumerate() {
+        if idx > 0 {
+            uri.push('&');
+        }
+        uri.push_str(name);
+        uri.push('=');
+        for byte in value.iter() {
+            write!(uri, "%{byte:02X}").unwrap();
+        }
+    }
+    uri.parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_v6_handlers_survive_random_input() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+    let alphabet = b"0123456789abcdefABCDEF:.%/ fz";
+    for _ in 0..1000 {
+        let mut input = || -> Vec<u8> {
+            let len = rng.gen_range(0..48);
+            (0..len)
+                .map(|_| match rng.gen_bool(0.9) {
+                    true => alphabet[rng.gen_range(0..alphabet.len())],
+                    false => rng.gen(),
+                })
+                .collect()
+        };
+        let (a, b) = (input(), input());
+
+        let uri = v6_query_uri("/2/v6/dest", [("from", &a), ("key", &b)]);
+        if let Ok(params) = Query::<V6DestQParams>::try_from_uri(&uri) {
+            assert!(v6_dest(params).await.parse::<Ipv6Addr>().is_ok());
+        }
+        let uri = v6_query_uri("/2/v6/key", [("from", &a), ("to", &b)]);
+        if let Ok(params) = Query::<V6KeyQParams>::try_from_uri(&uri) {
+            assert!(v6_key(params).await.parse::<Ipv6Addr>().is_ok());
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_v6_mapped_output_stays_hex() {
+    let dest = v6_dest(Query(V6DestQParams {
+        from: "::ffff:0:0".parse().unwrap(),
+        key: "::".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(dest, "::ffff:0:0");
+
+    let key = v6_key(Query(V6KeyQParams {
+        from: "::1:1".parse().unwrap(),
+        to: "::ffff:c000:201".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(key, "::ffff:c001:200");
+
+    let dest = v6_add_dest(Query(V6DestQParams {
+        from: "::ffff:c000:201".parse().unwrap(),
+        key: "::".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(dest, "::ffff:c000:201");
+}
+
+#[tokio::test]
+async fn test_v6_output_is_compressed() {
+    let dest = |from: &str, key: &str| {
+        v6_dest(Query(V6DestQParams {
+            from: from.parse().unwrap(),
+            key: key.parse().unwrap(),
+        }))
+    };
+    let key = |from: &str, to: &str| {
+        v6_key(Query(V6KeyQParams {
+            from: from.parse().unwrap(),
+            to: to.parse().unwrap(),
+        }))
+    };
+
+    assert_eq!(dest("2001:db8::", "2001:db8::").await, "::");
+    assert_eq!(key("2001:db8::", "2001:db8::").await, "::");
+    assert_eq!(dest("2001:db8::1", "::1").await, "2001:db8::");
+    assert_eq!(key("ffff::", "ffff::1:0:0:1").await, "::1:0:0:1");
+
+    // A mapped result is still a well-formed IPv6 address, just not dotted
+    let mapped = dest("::ffff:0:0", "::c000:201").await;
+    assert_eq!(mapped, "::ffff:c000:201");
+    assert_eq!(
+        mapped.parse::<Ipv6Addr>().unwrap(),
+        Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped()
+    );
+}
+
+#[tokio::test]
+async fn test_v6_add_carries() {
+    let dest = v6_add_dest(Query(V6DestQParams {
+        from: "::ffff".parse().unwrap(),
+        key: "::1".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(dest, "::1:0");
+
+    let key = v6_add_key(Query(V6KeyQParams {
+        from: "::ffff".parse().unwrap(),
+        to: "::1:0".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(key, "::1");
+}
+
+#[tokio::test]
+async fn test_v6_add_wraps() {
+    let dest = v6_add_dest(Query(V6DestQParams {
+        from: "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff".parse().unwrap(),
+        key: "::2".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(dest, "::1");
+
+    let key = v6_add_key(Query(V6KeyQParams {
+        from: "::1".parse().unwrap(),
+        to: "::".parse().unwrap(),
+    }))
+    .await;
+    assert_eq!(key, "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff");
+}
+
+#[test]
+fn test_cidr_blocks() {
+    let ip = Ipv4Addr::new(192, 168, 1, 77);
+
+    assert_eq!(
+        cidr_block(ip, 24),
+        Some(Cidr {
+            network: Ipv4Addr::new(192, 168, 1, 0),
+            broadcast: Some(Ipv4Addr::new(192, 168, 1, 255)),
+            first_host: Ipv4Addr::new(192, 168, 1, 1),
+            last_host: Ipv4Addr::new(192, 168, 1, 254),
+            hosts: 254,
+        })
+    );
+    assert_eq!(
+        cidr_block(ip, 32),
+        Some(Cidr {
+            network: ip,
+            broadcast: None,
+            first_host: ip,
+            last_host: ip,
+            hosts: 1,
+        })
+    );
+    assert_eq!(
+        cidr_block(ip, 31),
+        Some(Cidr {
+            network: Ipv4Addr::new(192, 168, 1, 76),
+            broadcast: None,
+            first_host: Ipv4Addr::new(192, 168, 1, 76),
+            last_host: Ipv4Addr::new(192, 168, 1, 77),
+            hosts: 2,
+        })
+    );
+    assert_eq!(
+        cidr_block(ip, 0),
+        Some(Cidr {
+            network: Ipv4Addr::UNSPECIFIED,
+            broadcast: Some(Ipv4Addr::BROADCAST),
+            first_host: Ipv4Addr::new(0, 0, 0, 1),
+            last_host: Ipv4Addr::new(255, 255, 255, 254),
+            hosts: u32::MAX - 1,
+        })
+    );
+    assert_eq!(cidr_block(ip, 33), None);
+}
+
+#[test]
+fn test_classify_ip() {
+    let classes = |ip: &str| classify_ip(ip.parse().unwrap());
+
+    assert_eq!(classes("127.0.0.1"), ["loopback"]);
+    assert_eq!(classes("10.0.0.1"), ["private"]);
+    assert_eq!(classes("224.0.0.1"), ["multicast"]);
+    assert_eq!(classes("8.8.8.8"), ["public"]);
+    assert_eq!(classes("255.255.255.255"), ["broadcast"]);
+    assert_eq!(classes("::1"), ["loopback"]);
+    assert_eq!(classes("ff02::1"), ["multicast"]);
+    assert_eq!(classes("fe80::1"), ["link_local"]);
+}
+
+#[tokio::test]
+async fn test_batch_dest_mixed_entries() {
+    let entries = serde_json::from_str(
+        r#"[
+            {"from": "10.0.0.0", "key": "1.2.3.255"},
+            {"from": "notanip", "key": "1.2.3.4"},
+            {"from": "10.0.0.0"},
+            {"from": "128.128.33.0", "key": "255.0.255.33"}
+        ]"#,
+    )
+    .unwrap();
+
+    let Json(dests) = batch_dest(Json(entries)).await.unwrap();
+
+    assert_eq!(
+        dests,
+        [
+            BatchDest {
+                dest: Some(Ipv4Addr::new(11, 2, 3, 255))
+            },
+            BatchDest { dest: None },
+            BatchDest { dest: None },
+            BatchDest {
+                dest: Some(Ipv4Addr::new(127, 128, 32, 33))
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_batch_key_cap() {
+    let entry = || BatchKeyEntry {
+        from: Some("10.0.0.0".to_owned()),
+        to: Some("11.2.3.255".to_owned()),
+    };
+
+    let full = batch_key(Json((0..MAX_BATCH_SIZE).map(|_| entry()).collect())).await;
+    assert_eq!(
+        full.unwrap().0[0],
+        BatchKey {
+            key: Some(Ipv4Addr::new(1, 2, 3, 255))
+        }
+    );
+
+    let over = batch_key(Json((0..=MAX_BATCH_SIZE).map(|_| entry()).collect())).await;
+    assert_eq!(over.err(), Some(StatusCode::PAYLOAD_TOO_LARGE));
+}
+
+#[tokio::test]
+async fn test_ip_int_round_trip() {
+    let value = |ip: Ipv4Addr| async move { ip2int(Query(Ip2IntQParams { ip })).await.0.value };
+
+    assert_eq!(value(Ipv4Addr::UNSPECIFIED).await, 0);
+    assert_eq!(value(Ipv4Addr::BROADCAST).await, 4294967295);
+
+    let ip = Ipv4Addr::new(192, 168, 10, 7);
+    let Json(IpAddress { ip: round_trip }) = int2ip(Query(Int2IpQParams {
+        value: value(ip).await,
+    }))
+    .await;
+    assert_eq!(round_trip, ip);
+}
+
+#[tokio::test]
+async fn test_cidr_overlap() {
+    let overlap_of = |a: &str, b: &str| {
+        let payload = OverlapPayload {
+            a: a.to_owned(),
+            b: b.to_owned(),
+        };
+        async move { overlap(Json(payload)).await.map(|json| json.0) }
+    };
+    let overlapping = |intersection: &str| {
+        Ok(Overlap {
+            overlaps: true,
+            intersection: Some(intersection.to_owned()),
+        })
+    };
+
+    // full containment
+    assert_eq!(
+        overlap_of("192.168.0.0/24", "192.168.0.128/25").await,
+        overlapping("192.168.0.128/25")
+    );
+    // containment where the looser block is given with host bits set
+    assert_eq!(
+        overlap_of("10.1.2.3/8", "10.1.0.0/16").await,
+        overlapping("10.1.0.0/16")
+    );
+    // adjacent, non-overlapping
+    assert_eq!(
+        overlap_of("192.168.0.0/25", "192.168.0.128/25").await,
+        Ok(Overlap {
+            overlaps: false,
+            intersection: None,
+        })
+    );
+    // identical
+    assert_eq!(
+        overlap_of("172.16.0.0/12", "172.16.0.0/12").await,
+        overlapping("172.16.0.0/12")
+    );
+    assert_eq!(
+        overlap_of("172.16.0.0/33", "172.16.0.0/12").await,
+        Err(StatusCode::BAD_REQUEST)
+    );
+}
+
+#[test]
+fn test_step_ip_wraps() {
+    let step = |ip: &str, forward| step_ip(ip.parse().unwrap(), forward).to_string();
+
+    assert_eq!(step("255.255.255.255", true), "0.0.0.0");
+    assert_eq!(step("0.0.0.0", false), "255.255.255.255");
+    assert_eq!(step("10.0.0.255", true), "10.0.1.0");
+    assert_eq!(step("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff", true), "::");
+    assert_eq!(step("::", false), "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff");
+}
+
+#[test]
+fn test_query_flag() {
+    let params = |query: &str| {
+        Query::<StepQParams>::try_from_uri(&format!("/2/next?{query}").parse().unwrap())
+            .map(|params| params.0.v6)
+            .ok()
+    };
+
+    assert_eq!(params("ip=::1&v6=1"), Some(true));
+    assert_eq!(params("ip=::1&v6=true"), Some(true));
+    assert_eq!(params("ip=1.1.1.1"), Some(false));
+    assert_eq!(params("ip=1.1.1.1&v6=yes"), None);
+}
+
+#[test]
+fn test_multicast_validity() {
+    let validity = |ip: &str| multicast_validity(ip.parse().unwrap());
+    let expected = |valid, scope, reserved| MulticastValidity {
+        valid,
+        scope,
+        reserved,
+    };
+
+    assert_eq!(validity("224.0.0.1"), expected(false, "link_local", true));
+    assert_eq!(
+        validity("239.255.255.255"),
+        expected(true, "site_local", false)
+    );
+    assert_eq!(validity("8.8.8.8"), expected(false, "unknown", false));
+    assert_eq!(validity("ff02::1"), expected(true, "link_local", false));
+    assert_eq!(validity("ff0e::1"), expected(true, "global", false));
+    assert_eq!(validity("ff0f::1"), expected(false, "unknown", true));
+}
+
+#[tokio::test]
+async fn test_v4_params_name_bad_field() {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    let router = Router::new()
+        .route("/2/dest", get(dest))
+        .route("/2/key", get(key));
+    let get = |uri: &'static str| {
+        let router = router.clone();
+        async move {
+            let res = router
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = res.status();
+            let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+    };
+    let bad = |field: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid IPv4 address: {field}\n"),
+        )
+    };
+    let missing = |field: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Missing query parameter: {field}\n"),
+        )
+    };
+
+    assert_eq!(
+        get("/2/dest?from=10.0.0.0&key=1.2.3.255").await,
+        (StatusCode::OK, "11.2.3.255".to_owned())
+    );
+    assert_eq!(get("/2/dest?from=notanip&key=1.2.3.255").await, bad("from"));
+    assert_eq!(get("/2/dest?from=10.0.0.0&key=256.0.0.1").await, bad("key"));
+    assert_eq!(get("/2/dest").await, missing("from"));
+    assert_eq!(get("/2/key?from=10.0.0.0").await, missing("to"));
+    assert_eq!(get("/2/key").await, missing("from"));
+}
+
+#[tokio::test]
+async fn test_capitalised_query_aliases() {
+    let parts = |uri: &str| {
+        axum::http::Request::get(uri)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    };
+    let dest_params = |uri: &'static str| async move {
+        DestQParams::from_request_parts(&mut parts(uri), &())
+            .await
+            .map(|params| (params.from, params.key))
+    };
+    let key_params = |uri: &'static str| async move {
+        KeyQParams::from_request_parts(&mut parts(uri), &())
+            .await
+            .map(|params| (params.from, params.to))
+    };
+    let (a, b) = (Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(1, 2, 3, 255));
+
+    assert_eq!(
+        dest_params("/2/dest?From=10.0.0.0&Key=1.2.3.255").await,
+        Ok((a, b))
+    );
+    assert_eq!(
+        dest_params("/2/dest?from=10.0.0.0&Key=1.2.3.255").await,
+        Ok((a, b))
+    );
+    assert_eq!(
+        key_params("/2/key?From=10.0.0.0&To=1.2.3.255").await,
+        Ok((a, b))
+    );
+    assert_eq!(
+        key_params("/2/key?FROM=10.0.0.0&to=1.2.3.255").await,
+        Err((
+            StatusCode::BAD_REQUEST,
+            "Missing query parameter: from\n".to_owned()
+        ))
+    );
+
+    let v6 = |uri: &str| uri.parse::<axum::http::Uri>().unwrap();
+    let Query(dest) = Query::<V6DestQParams>::try_from_uri(&v6("/?From=::1&Key=::2")).unwrap();
+    assert_eq!(
+        (dest.from, dest.key),
+        (Ipv6Addr::from(1), Ipv6Addr::from(2))
+    );
+    let Query(key) = Query::<V6KeyQParams>::try_from_uri(&v6("/?From=::1&To=::2")).unwrap();
+    assert_eq!((key.from, key.to), (Ipv6Addr::from(1), Ipv6Addr::from(2)));
+    let missing = Query::<V6KeyQParams>::try_from_uri(&v6("/?From=::1"))
+        .err()
+        .unwrap();
+    assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+    assert!(missing.body_text().contains("missing field `to`"));
 }
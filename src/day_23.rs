@@ -1,27 +1,106 @@
+use std::{collections::HashSet, convert::Infallible, sync::LazyLock, time::Duration};
+
 use axum::{
     extract::{Multipart, Path, Query},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{
+        sse::{Event, KeepAlive},
+        Html, IntoResponse, Response, Sse,
+    },
+    routing::{get, post},
+    Router,
 };
+use futures_util::Stream;
 use maud::html;
 use serde::Deserialize;
-use toml::{map::Map, Value};
+use tokio::sync::broadcast::{self, error::RecvError};
+use toml::Value;
+
+/// Fan-out of ornament state changes to every `/23/events` subscriber.
+static ORNAMENT_EVENTS: LazyLock<broadcast::Sender<String>> =
+    LazyLock::new(|| broadcast::channel(64).0);
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StarQuery {
+    size: Option<u16>,
+}
+
+/// Star outline as percentages of the bounding box, matching the `#star` clip-path in `23.html`.
+const STAR_POINTS: [(u16, u16); 10] = [
+    (50, 0),
+    (61, 35),
+    (98, 35),
+    (68, 57),
+    (79, 91),
+    (50, 70),
+    (21, 91),
+    (32, 57),
+    (2, 35),
+    (39, 35),
+];
+
+#[utoipa::path(get, path = "/23/star", params(StarQuery), responses((status = 200, content_type = "image/svg+xml", body = String), (status = 400, description = "Size outside 1..=200")))]
+pub async fn star(Query(query): Query<StarQuery>) -> Response {
+    let size = query.size.unwrap_or(50);
+    if !(1..=200).contains(&size) {
+        return (StatusCode::BAD_REQUEST,).into_response();
+    }
+    let points = STAR_POINTS
+        .iter()
+        .map(|(x, y)| {
+            let scale = |pct: u16| f32::from(pct * size) / 100.0;
+            format!("{},{}", scale(*x), scale(*y))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
 
-pub async fn star() -> Response {
     html! {
-        div #star .lit {  }
+        svg #star .lit xmlns="http://www.w3.org/2000/svg" width=(size) height=(size) viewBox={"0 0 "(size)" "(size)} {
+            polygon points=(points) {}
+        }
     }
     .into_string()
     .into_response()
 }
 
-pub async fn colour_present(Path(colour): Path<String>) -> Result<Html<String>, StatusCode> {
-    let next_colour = match colour.as_str() {
-        "red" => "blue",
-        "blue" => "purple",
-        "purple" => "red",
-        _ => return Err(StatusCode::IM_A_TEAPOT),
-    };
+/// A present's colour; anything else is turned away by `Path` with a `400`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentColour {
+    Red,
+    Blue,
+    Purple,
+    Gold,
+    White,
+}
+
+impl PresentColour {
+    fn as_str(self) -> &'static str {
+        match self {
+            PresentColour::Red => "red",
+            PresentColour::Blue => "blue",
+            PresentColour::Purple => "purple",
+            PresentColour::Gold => "gold",
+            PresentColour::White => "white",
+        }
+    }
+
+    /// The colour a click turns the present into.
+    fn next_colour(self) -> Self {
+        match self {
+            PresentColour::Red => PresentColour::Blue,
+            PresentColour::Blue => PresentColour::Purple,
+            PresentColour::Purple => PresentColour::Gold,
+            PresentColour::Gold => PresentColour::White,
+            PresentColour::White => PresentColour::Red,
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/23/present/{c}", params(("c" = PresentColour, Path, description = "Current colour")), responses((status = 200, content_type = "text/html", body = String), (status = 400, description = "Unknown colour")))]
+pub async fn colour_present(Path(colour): Path<PresentColour>) -> Html<String> {
+    let (colour, next_colour) = (colour.as_str(), colour.next_colour().as_str());
 
     let html = html! {
         div ."present" .(colour) hx-get={"/23/present/"(next_colour)""} hx-swap="outerHTML" {
@@ -33,17 +112,33 @@ pub async fn colour_present(Path(colour): Path<String>) -> Result<Html<String>,
     }
     .into_string();
 
-    Ok(Html(html))
+    Html(html)
 }
 
+#[utoipa::path(
+    get,
+    path = "/23/ornament/{state}/{n}",
+    params(("state" = String, Path, description = "`on` or `off`"), ("n" = String, Path, description = "Ornament id")),
+    responses((status = 200, content_type = "text/html", body = String), (status = 400, description = "Unknown state, or an id that isn't 1 to 32 of `[a-zA-Z0-9_-]`"))
+)]
 pub async fn ornament(
     Path((state, n)): Path<(String, String)>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, (StatusCode, &'static str)> {
     let (next_state, current_state) = match &*state {
         "on" => ("off", "ornament on"),
         "off" => ("on", "ornament"),
-        _ => return Err(StatusCode::IM_A_TEAPOT),
+        _ => return Err((StatusCode::BAD_REQUEST, "Invalid ornament state\n")),
     };
+    // maud escapes attributes anyway; this keeps ids usable as HTML ids and in the `hx-get` path
+    let valid_id = (1..=32).contains(&n.len())
+        && n.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    if !valid_id {
+        return Err((StatusCode::BAD_REQUEST, "Invalid ornament ID\n"));
+    }
+
+    // Sending only fails when nobody is subscribed, which is fine
+    let _ = ORNAMENT_EVENTS.send(serde_json::json!({ "n": n, "state": state }).to_string());
 
     let html = html! {
         div
@@ -59,6 +154,80 @@ pub async fn ornament(
     Ok(Html(html))
 }
 
+/// Largest `top`/`left` offset in px, so ornaments stay inside the tree container.
+const MAX_ORNAMENT_POS: u8 = 200;
+
+#[utoipa::path(get, path = "/23/events", responses((status = 200, description = "`ornament_change` events", content_type = "text/event-stream", body = String)))]
+pub async fn events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut rx = ORNAMENT_EVENTS.subscribe();
+    let stream = async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(data) => yield Ok(Event::default().event("ornament_change").data(data)),
+                // A slow client skips the changes it missed rather than disconnecting
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .event(Event::default().event("ping")),
+    )
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TreeQuery {
+    height: Option<usize>,
+}
+
+const ORNAMENT_COLOURS: [&str; 3] = ["red", "blue", "purple"];
+
+#[utoipa::path(get, path = "/23/tree", params(TreeQuery), responses((status = 200, content_type = "text/html", body = String), (status = 400, description = "Height outside 1..=20")))]
+pub async fn tree(Query(query): Query<TreeQuery>) -> Result<Html<String>, StatusCode> {
+    let height = query.height.unwrap_or(5);
+    if !(1..=20).contains(&height) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let width = 2 * height - 1;
+
+    // Row `k` (1-based) holds `2k - 1` cells, centred with spaces. Every third cell of the
+    // flattened grid is an ornament, so the output is stable for a given height.
+    let html = html! {
+        pre .ascii-tree {
+            @for row in 0..height {
+                (" ".repeat(height - row - 1))
+                @for col in 0..(2 * row + 1) {
+                    @let cell = row * width + col;
+                    @if row == 0 {
+                        "⭐"
+                    } @else if cell % 3 == 0 {
+                        span class={"ornament " (ORNAMENT_COLOURS[cell / 3 % 3])} { "🔴" }
+                    } @else {
+                        "🌲"
+                    }
+                }
+                "\n"
+            }
+        }
+    }
+    .into_string();
+
+    Ok(Html(html))
+}
+
+/// A `Cargo.lock` in format v2 (no `version` key) or v3 and later, which keep the same
+/// `[[package]]` layout. Packages stay untyped so one odd entry doesn't sink the file.
+#[derive(Deserialize)]
+struct LockfileV3 {
+    version: Option<i64>,
+    #[serde(default)]
+    package: Vec<Value>,
+}
+
 #[derive(Deserialize)]
 struct Package {
     _name: Option<String>,
@@ -67,51 +236,90 @@ struct Package {
     checksum: String,
 }
 impl Package {
-    fn cal(self) -> Option<(String, u8, u8)> {
+    fn cal(self, index: usize) -> Result<(String, u8, u8), String> {
         // We expect at least 10 hex characters
         if self.checksum.len() < 10 {
-            return None;
+            return Err(format!(
+                "Invalid checksum length for package at index {index}: got {} chars, need ≥10",
+                self.checksum.len()
+            ));
         }
+        let invalid_checksum = || format!("Invalid checksum for package at index {index}");
 
-        let color = &self.checksum[0..6]; // #RRGGBB
-        let top_hex = &self.checksum[6..8];
-        let left_hex = &self.checksum[8..10];
+        // The length above is in bytes, so a multi-byte character can still straddle a boundary
+        let slice = |range| self.checksum.get(range).ok_or_else(invalid_checksum);
+        let color = slice(0..6)?; // #RRGGBB
+        let top_hex = slice(6..8)?;
+        let left_hex = slice(8..10)?;
 
-        // Ensure only valid hex chars in the color portion
+        // Ensure only valid hex chars in the color portion; this is also what keeps `"` or `;`
+        // from breaking out of the style attribute (maud escapes the rest)
         if !color.chars().all(|c| c.is_ascii_hexdigit()) {
-            return None;
+            return Err(invalid_checksum());
         }
 
-        // Parse top and left as hex into u8
-        let top = u8::from_str_radix(top_hex, 16).ok()?;
-        let left = u8::from_str_radix(left_hex, 16).ok()?;
+        // Parse top and left as hex into u8, clamped to the container
+        let top = u8::from_str_radix(top_hex, 16)
+            .map_err(|_| invalid_checksum())?
+            .min(MAX_ORNAMENT_POS);
+        let left = u8::from_str_radix(left_hex, 16)
+            .map_err(|_| invalid_checksum())?
+            .min(MAX_ORNAMENT_POS);
 
-        Some((format!("#{color}"), top, left))
+        // Cargo writes checksums in lowercase, but the CSS always gets uppercase hex
+        Ok((format!("#{}", color.to_ascii_uppercase()), top, left))
     }
 }
 
-pub async fn lockfile(mut multipart: Multipart) -> Result<Html<String>, StatusCode> {
+#[utoipa::path(
+    post,
+    path = "/23/lockfile",
+    request_body(content = String, description = "A `lockfile` field holding a Cargo.lock", content_type = "multipart/form-data"),
+    responses((status = 200, content_type = "text/html", body = String), (status = 400, description = "Missing `lockfile` field or unparsable lockfile"), (status = 422, description = "Packages with invalid checksums"))
+)]
+pub async fn lockfile(mut multipart: Multipart) -> Result<Html<String>, Response> {
     let mut htmls = Vec::new();
+    let mut errors = Vec::new();
+    // The same package may appear in several parts but only gets one ornament
+    let mut seen_checksums = HashSet::new();
     while let Some(field) = multipart
         .next_field()
         .await
-        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .map_err(|_| StatusCode::BAD_REQUEST.into_response())?
     {
-        let data = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if field.name() != Some("lockfile") {
+            return Err(StatusCode::BAD_REQUEST.into_response());
+        }
+        let data = field
+            .text()
+            .await
+            .map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
 
-        let payload: Map<String, Value> =
-            toml::from_str(&data).map_err(|_| StatusCode::BAD_REQUEST)?;
-        let packages = payload["package"].as_array().unwrap();
+        let lockfile: LockfileV3 =
+            toml::from_str(&data).map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
+        if !matches!(lockfile.version, None | Some(3..=4)) {
+            return Err(StatusCode::BAD_REQUEST.into_response());
+        }
 
-        for package in packages {
-            if let Ok(payload) = package.clone().try_into::<Package>() {
-                let d = payload.cal().ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
-                htmls.push(d);
+        // Keep going after a bad checksum so every failing package is reported at once. Packages
+        // without a checksum, such as path dependencies, get no ornament
+        for (index, package) in lockfile.package.into_iter().enumerate() {
+            if let Ok(payload) = package.try_into::<Package>() {
+                if !seen_checksums.insert(payload.checksum.clone()) {
+                    continue;
+                }
+                match payload.cal(index) {
+                    Ok(d) => htmls.push(d),
+                    Err(error) => errors.push(error),
+                }
             }
         }
     }
+    if !errors.is_empty() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, errors.join("\n")).into_response());
+    }
     if htmls.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into_response());
     }
     let html = htmls
         .into_iter()
@@ -127,3 +335,418 @@ pub async fn lockfile(mut multipart: Multipart) -> Result<Html<String>, StatusCo
 
     Ok(Html(html))
 }
+
+/// Every `/23` route, relative to where `main.rs` nests them.
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/star", get(star))
+        .route("/present/:c", get(colour_present))
+        .route("/ornament/:state/:n", get(ornament))
+        .route("/events", get(events))
+        .route("/tree", get(tree))
+        .route("/lockfile", post(lockfile))
+}
+
+#[cfg(test)]
+async fn send(req: axum::http::Request<axum::body::Body>) -> (StatusCode, String) {
+    use tower::ServiceExt;
+
+    let res = Router::new()
+        .nest("/23", router())
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[cfg(test)]
+async fn post_lockfile(parts: &[(&str, &str)]) -> (StatusCode, String) {
+    use axum::{body::Body, extract::FromRequest, http::Request};
+
+    let mut body = String::new();
+    for (name, content) in parts {
+        body.push_str(&format!(
+            "--BOUNDARY\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{content}\r\n"
+        ));
+    }
+    body.push_str("--BOUNDARY--\r\n");
+    let req = Request::post("/23/lockfile")
+        .header("Content-Type", "multipart/form-data; boundary=BOUNDARY")
+        .body(Body::from(body))
+        .unwrap();
+    let multipart = Multipart::from_request(req, &()).await.unwrap();
+
+    let res = lockfile(multipart).await.into_response();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_lockfile_reports_short_checksums() {
+    let lockfile = r#"
+[[package]]
+name = "good"
+checksum = "337789faa0372648a8ac286b2f92a53121fe118f12e29009ac504872a5413cc6"
+
+[[package]]
+name = "short"
+checksum = "337789"
+"#;
+
+    assert_eq!(
+        post_lockfile(&[("lockfile", lockfile)]).await,
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Invalid checksum length for package at index 1: got 6 chars, need ≥10".to_owned()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_lockfile_deduplicates_parts() {
+    let lockfile = r#"
+[[package]]
+name = "good"
+checksum = "337789faa0372648a8ac286b2f92a53121fe118f12e29009ac504872a5413cc6"
+"#;
+
+    let (status, body) = post_lockfile(&[("lockfile", lockfile), ("lockfile", lockfile)]).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("<div").count(), 1);
+    assert_eq!(
+        post_lockfile(&[("other", lockfile)]).await.0,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn test_lockfile_v3_skips_path_dependencies() {
+    let lockfile = r#"
+# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "shuttlings-cch24"
+version = "0.1.0"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.216"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "0b9781016e935a97e8beecf0c933758c97a5520d32930e460142b4cd80c6338e"
+
+[[package]]
+name = "local-helper"
+version = "0.1.0"
+
+[[package]]
+name = "toml"
+version = "0.8.19"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "a1ed1f98e3fdc28d6d910e6737ae6ab1a93bf1985935a1193e68f93eeb68d24e"
+"#;
+
+    let (status, body) = post_lockfile(&[("lockfile", lockfile)]).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("<div").count(), 2);
+    assert!(body.contains("background-color:#0B9781"));
+    assert!(body.contains("background-color:#A1ED1F"));
+    assert_eq!(
+        post_lockfile(&[("lockfile", &lockfile.replace("version = 3", "version = 9"))])
+            .await
+            .0,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        post_lockfile(&[("lockfile", "version = 3\n")]).await.0,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[test]
+fn test_ornament_position_clamped() {
+    let position = |checksum: &str| {
+        Package {
+            _name: None,
+            _source: None,
+            _version: None,
+            checksum: checksum.to_owned(),
+        }
+        .cal(0)
+    };
+
+    assert_eq!(position("abcdefc8c8"), Ok(("#ABCDEF".to_owned(), 200, 200)));
+    assert_eq!(position("abcdefc9ff"), Ok(("#ABCDEF".to_owned(), 200, 200)));
+    assert_eq!(position("abcdef0010"), Ok(("#ABCDEF".to_owned(), 0, 16)));
+}
+
+#[test]
+fn test_checksum_slicing_never_panics() {
+    let cal = |checksum: &str| {
+        Package {
+            _name: None,
+            _source: None,
+            _version: None,
+            checksum: checksum.to_owned(),
+        }
+        .cal(3)
+    };
+
+    for checksum in ["abcdef00", "abcdef001"] {
+        assert_eq!(
+            cal(checksum),
+            Err(format!(
+                "Invalid checksum length for package at index 3: got {} chars, need ≥10",
+                checksum.len()
+            ))
+        );
+    }
+    assert_eq!(cal("abcdef0010"), Ok(("#ABCDEF".to_owned(), 0, 16)));
+    // Long enough in bytes, but each has a cut landing inside a two-byte `é`
+    for checksum in ["abcdeé0010", "abcdef0é10", "abcdef00é0"] {
+        assert_eq!(
+            cal(checksum),
+            Err("Invalid checksum for package at index 3".to_owned()),
+            "{checksum}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_lockfile_uppercases_colours() {
+    let lockfile = r#"
+[[package]]
+name = "lower"
+checksum = "aabbcc0a0b0000000000000000000000000000000000000000000000000000"
+
+[[package]]
+name = "mixed"
+checksum = "aAbBcC0a0b0000000000000000000000000000000000000000000000000000"
+"#;
+
+    let (status, body) = post_lockfile(&[("lockfile", lockfile)]).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("background-color:#AABBCC").count(), 2);
+    assert!(!body.contains("#aabbcc"));
+}
+
+#[tokio::test]
+async fn test_lockfile_rejects_css_injection() {
+    let lockfile = r#"
+[[package]]
+name = "evil"
+checksum = "a\";}x{0000"
+"#;
+
+    let (status, body) = post_lockfile(&[("lockfile", lockfile)]).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(!body.contains("<div"));
+}
+
+#[tokio::test]
+async fn test_ornament_id_validation() {
+    let ornament_html = |state: &str, n: String| ornament(Path((state.to_owned(), n)));
+    let invalid_id = Err((StatusCode::BAD_REQUEST, "Invalid ornament ID\n"));
+
+    let Html(html) = ornament_html("on", "tree-top_1".to_owned()).await.unwrap();
+    assert!(html.contains(r#"id="ornamenttree-top_1""#));
+    assert!(ornament_html("off", "a".repeat(32)).await.is_ok());
+
+    for n in [
+        r#""><script>alert(1)</script>"#.to_owned(),
+        String::new(),
+        "a".repeat(33),
+        "élan".to_owned(),
+    ] {
+        assert_eq!(ornament_html("on", n).await.map(|_| ()), invalid_id);
+    }
+    assert_eq!(
+        ornament_html("dim", "1".to_owned()).await.map(|_| ()),
+        Err((StatusCode::BAD_REQUEST, "Invalid ornament state\n"))
+    );
+}
+
+#[cfg(test)]
+async fn get_present(uri: &str) -> (StatusCode, String) {
+    send(
+        axum::http::Request::get(uri)
+            .body(axum::body::Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_present_colour_cycle() {
+    for (colour, next) in [
+        ("red", "blue"),
+        ("blue", "purple"),
+        ("purple", "gold"),
+        ("gold", "white"),
+        ("white", "red"),
+    ] {
+        let (status, html) = get_present(&format!("/23/present/{colour}")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(&format!(r#"class="present {colour}""#)));
+        assert!(html.contains(&format!(r#"hx-get="/23/present/{next}""#)));
+    }
+    for colour in ["yellow", "Red", "green"] {
+        let (status, _) = get_present(&format!("/23/present/{colour}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_events_broadcasts_ornament_changes() {
+    use futures_util::StreamExt;
+
+    let mut body = events()
+        .await
+        .into_response()
+        .into_body()
+        .into_data_stream();
+
+    let _ = ornament(Path(("on".to_owned(), "sse-test".to_owned()))).await;
+
+    let event = tokio::time::timeout(Duration::from_secs(1), async {
+        loop {
+            let chunk = body.next().await.unwrap().unwrap();
+            let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+            if chunk.contains("sse-test") {
+                return chunk;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        event,
+        "event: ornament_change\ndata: {\"n\":\"sse-test\",\"state\":\"on\"}\n\n"
+    );
+}
+
+#[tokio::test]
+async fn test_tree_heights() {
+    let tree_of = |height| async move { tree(Query(TreeQuery { height })).await };
+
+    let Html(html) = tree_of(Some(1)).await.unwrap();
+    assert_eq!(html, "<pre class=\"ascii-tree\">⭐\n</pre>");
+
+    for height in [5, 20] {
+        let Html(html) = tree_of(Some(height)).await.unwrap();
+        let cells = html.matches('🌲').count() + html.matches('🔴').count() + 1;
+        assert_eq!(html.matches('\n').count(), height);
+        assert_eq!(cells, height * height);
+        assert_eq!(html, tree_of(Some(height)).await.unwrap().0);
+    }
+    assert_eq!(
+        tree_of(None).await.unwrap().0.matches('\n').count(),
+        5,
+        "defaults to a height of 5"
+    );
+
+    assert_eq!(tree_of(Some(0)).await.err(), Some(StatusCode::BAD_REQUEST));
+    assert_eq!(tree_of(Some(21)).await.err(), Some(StatusCode::BAD_REQUEST));
+}
+
+#[tokio::test]
+async fn test_star_svg() {
+    use quick_xml::events::Event as XmlEvent;
+
+    let res = star(Query(StarQuery { size: Some(100) })).await;
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    let mut reader = quick_xml::Reader::from_reader(&body[..]);
+    let mut elements = Vec::new();
+    loop {
+        match reader.read_event().unwrap() {
+            XmlEvent::Start(element) | XmlEvent::Empty(element) => {
+                let attr = |name: &[u8]| {
+                    element
+                        .try_get_attribute(name)
+                        .unwrap()
+                        .map(|attr| String::from_utf8(attr.value.to_vec()).unwrap())
+                };
+                elements.push((
+                    String::from_utf8(element.name().as_ref().to_vec()).unwrap(),
+                    attr(b"viewBox"),
+                    attr(b"points").map(|points| points.split(' ').count()),
+                ));
+            }
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(
+        elements,
+        [
+            ("svg".to_owned(), Some("0 0 100 100".to_owned()), None),
+            ("polygon".to_owned(), None, Some(10)),
+        ]
+    );
+
+    for size in [0, 201] {
+        let res = star(Query(StarQuery { size: Some(size) })).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_nested_routes() {
+    use axum::{body::Body, http::Request};
+
+    let get = |uri: &str| send(Request::get(uri).body(Body::empty()).unwrap());
+    let lockfile = |body: &'static str| {
+        send(
+            Request::post("/23/lockfile")
+                .header("Content-Type", "multipart/form-data; boundary=B")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(get("/23/star").await.0, StatusCode::OK);
+    assert_eq!(get("/23/present/red").await.0, StatusCode::OK);
+    assert_eq!(get("/23/present/green").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(get("/23/ornament/on/tree-1").await.0, StatusCode::OK);
+    assert_eq!(
+        get("/23/ornament/dim/tree-1").await.0,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        lockfile(
+            "--B\r\nContent-Disposition: form-data; name=\"lockfile\"\r\n\r\n\
+             [[package]]\nchecksum = \"337789faa0372648a8ac286b2f92a53121fe118f\"\n\r\n--B--\r\n"
+        )
+        .await
+        .0,
+        StatusCode::OK
+    );
+    assert_eq!(
+        lockfile("--B\r\nContent-Disposition: form-data; name=\"other\"\r\n\r\nx\r\n--B--\r\n")
+            .await
+            .0,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(get("/star").await.0, StatusCode::NOT_FOUND);
+}
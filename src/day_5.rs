@@ -1,8 +1,16 @@
 use axum::{
     body::{Body, Bytes},
-    http::HeaderMap,
+    http::{HeaderMap, StatusCode},
     response::Response,
 };
+use indexmap::IndexMap;
+
+/// Upper bound on a manifest upload. The limit layer in `main.rs` enforces it on the raw body
+/// before `manifest` runs, so even a syntactically valid 10 MB TOML never reaches the parser.
+pub const MAX_MANIFEST_BYTES: usize = 1024 * 1024;
+
+/// Longest item name, in characters, that is accepted in an order.
+const MAX_ITEM_NAME_LEN: usize = 64;
 
 #[derive(serde::Deserialize, Debug)]
 struct Metadata {
@@ -15,7 +23,146 @@ struct Order {
     quantity: Option<toml::Value>,
 }
 
+#[derive(serde::Serialize, Debug, utoipa::ToSchema)]
+struct OrdersResponse {
+    orders: Vec<OrderLine>,
+    total: u64,
+}
+
+#[derive(serde::Serialize, Debug, utoipa::ToSchema)]
+struct OrderLine {
+    item: String,
+    quantity: u32,
+}
+
+/// An HTML form whose `manifest` field holds the TOML text.
+#[derive(serde::Deserialize, Debug)]
+struct ManifestForm {
+    manifest: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct XmlManifest {
+    package: Option<XmlPackage>,
+    metadata: Option<XmlMetadata>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct XmlPackage {
+    name: Option<String>,
+    version: Option<String>,
+    keywords: Option<XmlKeywords>,
+    metadata: Option<XmlMetadata>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct XmlKeywords {
+    #[serde(default)]
+    keyword: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct XmlMetadata {
+    orders: Option<XmlOrders>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct XmlOrders {
+    #[serde(default)]
+    order: Vec<XmlOrder>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct XmlOrder {
+    item: Option<String>,
+    quantity: Option<String>,
+}
+
+/// Parses TOML straight into a `Manifest`. `from_slice_with_metadata` would treat a manifest
+/// with neither `[package]` nor `[workspace]` as a legacy root-level package instead.
+fn manifest_from_toml(body: &[u8]) -> Option<cargo_manifest::Manifest<Metadata>> {
+    toml::from_str(std::str::from_utf8(body).ok()?).ok()
+}
+
+/// Parses a `<manifest>` document by rebuilding the equivalent TOML tree, so XML input goes
+/// through exactly the same `Manifest` validation as the other formats.
+fn manifest_from_xml(body: &[u8]) -> Option<cargo_manifest::Manifest<Metadata>> {
+    let xml: XmlManifest = quick_xml::de::from_reader(body).ok()?;
+
+    let mut package = toml::Table::new();
+    if let Some(xml_package) = xml.package {
+        if let Some(name) = xml_package.name {
+            package.insert("name".to_owned(), toml::Value::String(name));
+        }
+        if let Some(version) = xml_package.version {
+            package.insert("version".to_owned(), toml::Value::String(version));
+        }
+        if let Some(keywords) = xml_package.keywords {
+            let keywords = keywords
+                .keyword
+                .into_iter()
+                .map(toml::Value::String)
+                .collect();
+            package.insert("keywords".to_owned(), toml::Value::Array(keywords));
+        }
+        // `<metadata>` may live inside `<package>` (like TOML) or next to it
+        if let Some(metadata) = xml_package.metadata.or(xml.metadata) {
+            let orders = metadata
+                .orders
+                .map(|orders| orders.order)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|order| {
+                    let mut table = toml::Table::new();
+                    if let Some(item) = order.item {
+                        table.insert("item".to_owned(), toml::Value::String(item));
+                    }
+                    if let Some(quantity) = order.quantity {
+                        // XML has no integer type, so mirror TOML by parsing integral text
+                        let quantity = match quantity.trim().parse::<i64>() {
+                            Ok(integer) => toml::Value::Integer(integer),
+                            Err(_) => toml::Value::String(quantity),
+                        };
+                        table.insert("quantity".to_owned(), quantity);
+                    }
+                    toml::Value::Table(table)
+                })
+                .collect();
+            let mut metadata = toml::Table::new();
+            metadata.insert("orders".to_owned(), toml::Value::Array(orders));
+            package.insert("metadata".to_owned(), toml::Value::Table(metadata));
+        }
+    }
+
+    let mut manifest = toml::Table::new();
+    manifest.insert("package".to_owned(), toml::Value::Table(package));
+    toml::Value::Table(manifest).try_into().ok()
+}
+
 #[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/5/manifest",
+    request_body(description = "Cargo manifest", content(
+        (String = "application/toml"),
+        (String = "application/vnd.cargo+toml"),
+        (String = "text/toml"),
+        (String = "application/yaml"),
+        (String = "application/json"),
+        (String = "application/xml"),
+        (String = "application/x-www-form-urlencoded"),
+    )),
+    responses(
+        (status = 200, description = "Valid orders, one per line, or as JSON when accepted", content(
+            (String = "text/plain"),
+            (OrdersResponse = "application/json"),
+        )),
+        (status = 204, description = "No valid orders"),
+        (status = 400, description = "Invalid manifest, missing keyword, or neither `[package]` nor `[workspace]`"),
+        (status = 413, description = "Manifest larger than 1 MiB"),
+        (status = 415, description = "Unsupported content type, or a form without a `manifest` field"),
+    )
+)]
 pub async fn manifest(headers: HeaderMap, body: Bytes) -> Response {
     let invalid_response = || Response::builder().status(204).body(Body::empty()).unwrap();
     let invalid_media_type = || Response::builder().status(415).body(Body::empty()).unwrap();
@@ -25,6 +172,20 @@ pub async fn manifest(headers: HeaderMap, body: Bytes) -> Response {
             .body(Body::new("Invalid manifest".to_string()))
             .unwrap()
     };
+    let invalid_quantity = || {
+        Response::builder()
+            .status(400)
+            .body(Body::new(
+                "Invalid quantity: must be a positive integer".to_string(),
+            ))
+            .unwrap()
+    };
+    let no_package_section = || {
+        Response::builder()
+            .status(400)
+            .body(Body::new("No package section".to_string()))
+            .unwrap()
+    };
     let magic_keywrod_not_present = || {
         Response::builder()
             .status(400)
@@ -38,10 +199,8 @@ pub async fn manifest(headers: HeaderMap, body: Bytes) -> Response {
     };
 
     let manifest = match content_type.to_str().unwrap_or("") {
-        "application/toml" => {
-            let Ok(metadata) =
-                cargo_manifest::Manifest::<Metadata>::from_slice_with_metadata(&body)
-            else {
+        "application/toml" | "application/vnd.cargo+toml" | "text/toml" => {
+            let Some(metadata) = manifest_from_toml(&body) else {
                 return invalid_manifest();
             };
             metadata
@@ -60,26 +219,61 @@ pub async fn manifest(headers: HeaderMap, body: Bytes) -> Response {
             };
             metadata
         }
+        "application/xml" => {
+            let Some(metadata) = manifest_from_xml(&body) else {
+                return invalid_manifest();
+            };
+            metadata
+        }
+        "application/x-www-form-urlencoded" => {
+            let Ok(form) = serde_urlencoded::from_bytes::<ManifestForm>(&body) else {
+                return invalid_media_type();
+            };
+            let Some(metadata) = manifest_from_toml(form.manifest.as_bytes()) else {
+                return invalid_manifest();
+            };
+            metadata
+        }
         _ => return invalid_media_type(),
     };
 
-    let has_magic_keyword = manifest
-        .package
-        .as_ref()
-        .and_then(|x| x.keywords.as_ref())
-        .map(|x| match x {
-            cargo_manifest::MaybeInherited::Inherited { .. } => false,
-            cargo_manifest::MaybeInherited::Local(keyw) => keyw
-                .into_iter()
-                .find(|x| x.as_str() == "Christmas 2024")
-                .is_some(),
-        })
-        .unwrap_or_default();
+    if manifest.package.is_none() && manifest.workspace.is_none() {
+        return no_package_section();
+    }
+
+    let is_magic_keyword = |x: &String| x.to_lowercase() == "christmas 2024";
+    let has_magic_keyword = match manifest.package.as_ref() {
+        Some(package) => package
+            .keywords
+            .as_ref()
+            .map(|x| match x {
+                cargo_manifest::MaybeInherited::Inherited { .. } => false,
+                cargo_manifest::MaybeInherited::Local(keyw) => keyw.iter().any(is_magic_keyword),
+            })
+            .unwrap_or_default(),
+        // Workspace-only manifests carry their keywords in `[workspace.package]`
+        None => manifest
+            .workspace
+            .as_ref()
+            .and_then(|w| w.package.as_ref())
+            .and_then(|p| p.keywords.as_ref())
+            .is_some_and(|keyw| keyw.iter().any(is_magic_keyword)),
+    };
     if !has_magic_keyword {
         return magic_keywrod_not_present();
     }
 
-    let Some(metadata) = manifest.package.and_then(|m| m.metadata) else {
+    let metadata = match manifest.package {
+        Some(package) => package.metadata,
+        None => match manifest.workspace.and_then(|w| w.metadata) {
+            Some(metadata) => match metadata.try_into::<Metadata>() {
+                Ok(metadata) => Some(metadata),
+                Err(_) => return invalid_manifest(),
+            },
+            None => None,
+        },
+    };
+    let Some(metadata) = metadata else {
         dbg!("metadata manifest key not present");
         return invalid_response();
     };
@@ -89,38 +283,524 @@ pub async fn manifest(headers: HeaderMap, body: Bytes) -> Response {
         return invalid_response();
     };
 
-    let (counter, valid_orders) = orders
+    // Orders with malformed fields are skipped, but an integer quantity outside of
+    // `1..=u32::MAX` rejects the whole manifest
+    let Ok(orders) = orders
         .into_iter()
-        .filter_map(|order| {
-            let item = match order.item? {
-                toml::Value::String(s) => s,
-                _ => None?,
+        .map(|order| {
+            let quantity = match order.quantity {
+                Some(toml::Value::Integer(integer)) => match u32::try_from(integer) {
+                    Ok(quantity) if quantity > 0 => Some(quantity),
+                    _ => return Err(()),
+                },
+                _ => None,
             };
-            let quantity: u32 = match order.quantity? {
-                toml::Value::Integer(integer) => integer.try_into().ok()?,
-                _ => None?,
+            let item = match order.item {
+                Some(toml::Value::String(s))
+                    if (1..=MAX_ITEM_NAME_LEN).contains(&s.chars().count()) =>
+                {
+                    Some(s)
+                }
+                _ => None,
             };
-            Some((item, quantity))
+            Ok(item.zip(quantity))
         })
-        .fold((0, "".to_owned()), |(mut counter, mut acc), i| {
-            if counter > 0 {
-                acc.push_str("\n");
-            }
-            acc.push_str(i.0.as_str());
-            acc.push_str(": ");
-            acc.push_str(i.1.to_string().as_str());
-            counter += 1;
-            (counter, acc)
-        });
-
-    if counter == 0 {
+        .collect::<Result<Vec<_>, ()>>()
+    else {
+        return invalid_quantity();
+    };
+    // Repeated items are merged into the first line that names them
+    let mut merged = IndexMap::<String, u32>::new();
+    for (item, quantity) in orders.into_iter().flatten() {
+        let Some(sum) = merged
+            .get(&item)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(quantity)
+        else {
+            return Response::builder()
+                .status(400)
+                .body(Body::new(format!("Quantity overflow for item: {item}\n")))
+                .unwrap();
+        };
+        merged.insert(item, sum);
+    }
+    let orders = merged.into_iter().collect::<Vec<_>>();
+
+    if orders.is_empty() {
         dbg!("no valid orders");
         return invalid_response();
     };
 
-    dbg!(&valid_orders);
+    let wants_json = headers
+        .get("Accept")
+        .and_then(|x| x.to_str().ok())
+        .map(|x| {
+            x.split(',')
+                .any(|media| media.trim().starts_with("application/json"))
+        })
+        .unwrap_or_default();
+    // Summed as u64 so that many near-u32::MAX quantities cannot overflow
+    let total: u64 = orders
+        .iter()
+        .map(|(_, quantity)| u64::from(*quantity))
+        .sum();
+    if wants_json {
+        let orders = OrdersResponse {
+            orders: orders
+                .into_iter()
+                .map(|(item, quantity)| OrderLine { item, quantity })
+                .collect(),
+            total,
+        };
+        return Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json")
+            .body(Body::new(serde_json::to_string(&orders).unwrap()))
+            .unwrap();
+    }
+
+    let mut valid_orders = orders
+        .into_iter()
+        .map(|(item, quantity)| format!("{item}: {quantity}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    // `orders` can't be empty by now, so the total always goes on a line of its own
+    valid_orders.push_str(&format!("\nTotal: {total}\n"));
+
     Response::builder()
         .status(200)
         .body(Body::new(valid_orders))
         .unwrap()
 }
+
+/// Replaces the limit layer's `413` body with a manifest-specific message.
+pub async fn manifest_too_large(res: Response) -> Response {
+    if res.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return res;
+    }
+    Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .body(Body::new("Manifest too large\n".to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+async fn post_manifest(content_type: &'static str, body: &str) -> (u16, String) {
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", content_type.parse().unwrap());
+    send_manifest(headers, body).await
+}
+
+#[cfg(test)]
+async fn send_manifest(headers: HeaderMap, body: &str) -> (u16, String) {
+    let res = manifest(headers, Bytes::copy_from_slice(body.as_bytes())).await;
+    let status = res.status().as_u16();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_xml_manifest_matches_toml() {
+    let toml = post_manifest(
+        "application/toml",
+        r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+
+[[package.metadata.orders]]
+item = "Lego brick"
+quantity = 230
+"#,
+    )
+    .await;
+    let xml = post_manifest(
+        "application/xml",
+        r#"
+<manifest>
+    <package>
+        <name>not-a-gift-order</name>
+        <keywords><keyword>Christmas 2024</keyword></keywords>
+        <metadata>
+            <orders>
+                <order><item>Toy car</item><quantity>2</quantity></order>
+                <order><item>Lego brick</item><quantity>230</quantity></order>
+            </orders>
+        </metadata>
+    </package>
+</manifest>
+"#,
+    )
+    .await;
+
+    assert_eq!(
+        toml,
+        (200, "Toy car: 2\nLego brick: 230\nTotal: 232\n".to_owned())
+    );
+    assert_eq!(xml, toml);
+}
+
+#[tokio::test]
+async fn test_xml_manifest_sibling_metadata() {
+    let xml = post_manifest(
+        "application/xml",
+        r#"<manifest><package><name>x</name><keywords><keyword>Christmas 2024</keyword></keywords></package><metadata><orders><order><item>Toy</item><quantity>1</quantity></order><order><item>Bad</item><quantity>many</quantity></order></orders></metadata></manifest>"#,
+    )
+    .await;
+
+    assert_eq!(xml, (200, "Toy: 1\nTotal: 1\n".to_owned()));
+}
+
+#[tokio::test]
+async fn test_accept_json_orders() {
+    let body = r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+
+[[package.metadata.orders]]
+item = "Lego brick"
+quantity = 230
+"#;
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", "application/toml".parse().unwrap());
+    headers.insert("Accept", "text/plain".parse().unwrap());
+    let text = send_manifest(headers.clone(), body).await;
+
+    headers.insert("Accept", "application/json".parse().unwrap());
+    let json = send_manifest(headers, body).await;
+
+    assert_eq!(
+        text,
+        (200, "Toy car: 2\nLego brick: 230\nTotal: 232\n".to_owned())
+    );
+    assert_eq!(json.0, 200);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json.1).unwrap(),
+        serde_json::json!({"orders": [
+            {"item": "Toy car", "quantity": 2},
+            {"item": "Lego brick", "quantity": 230},
+        ], "total": 232})
+    );
+}
+
+#[cfg(test)]
+fn single_order_manifest(item: &str, quantity: &str) -> String {
+    format!(
+        r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = {item}
+quantity = {quantity}
+"#
+    )
+}
+
+#[tokio::test]
+async fn test_out_of_range_quantity_rejected() {
+    for quantity in ["0", "-1", "4294967296"] {
+        assert_eq!(
+            post_manifest(
+                "application/toml",
+                &single_order_manifest(r#""Toy""#, quantity)
+            )
+            .await,
+            (
+                400,
+                "Invalid quantity: must be a positive integer".to_owned()
+            )
+        );
+    }
+
+    // A quantity of the wrong type is still silently skipped
+    assert_eq!(
+        post_manifest(
+            "application/toml",
+            &single_order_manifest(r#""Toy""#, r#""1""#)
+        )
+        .await,
+        (204, "".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_magic_keyword_case_insensitive() {
+    let manifest_with_keyword = |keyword: &str| {
+        format!(
+            r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["{keyword}"]
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 1
+"#
+        )
+    };
+
+    for keyword in ["christmas 2024", "CHRISTMAS 2024", "Christmas 2024"] {
+        assert_eq!(
+            post_manifest("application/toml", &manifest_with_keyword(keyword)).await,
+            (200, "Toy: 1\nTotal: 1\n".to_owned())
+        );
+    }
+    assert_eq!(
+        post_manifest("application/toml", &manifest_with_keyword("Christmas2024")).await,
+        (400, "Magic keyword not provided".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_manifest_size_limit() {
+    use axum::{http::Request, routing::post, Router};
+    use tower::ServiceExt;
+    use tower_http::limit::RequestBodyLimitLayer;
+
+    let router = Router::new().route(
+        "/5/manifest",
+        post(manifest)
+            .layer(RequestBodyLimitLayer::new(MAX_MANIFEST_BYTES))
+            .layer(axum::middleware::map_response(manifest_too_large)),
+    );
+    let req = Request::post("/5/manifest")
+        .header("Content-Type", "application/toml")
+        .body(Body::from(vec![b'#'; 2 * 1024 * 1024]))
+        .unwrap();
+
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"Manifest too large\n");
+}
+
+#[tokio::test]
+async fn test_total_does_not_overflow_u32() {
+    let body = r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 4294967295
+
+[[package.metadata.orders]]
+item = "Gum"
+quantity = 7
+"#;
+
+    assert_eq!(
+        post_manifest("application/toml", body).await,
+        (
+            200,
+            "Toy: 4294967295\nGum: 7\nTotal: 4294967302\n".to_owned()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_item_name_length() {
+    let name = |len: usize| format!(r#""{}""#, "🎁".repeat(len));
+
+    assert_eq!(
+        post_manifest("application/toml", &single_order_manifest(&name(64), "1")).await,
+        (200, format!("{}: 1\nTotal: 1\n", "🎁".repeat(64)))
+    );
+    assert_eq!(
+        post_manifest("application/toml", &single_order_manifest(&name(65), "1")).await,
+        (204, "".to_owned())
+    );
+    assert_eq!(
+        post_manifest("application/toml", &single_order_manifest(&name(0), "1")).await,
+        (204, "".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_form_manifest_matches_toml() {
+    let manifest = single_order_manifest(r#""Toy car""#, "2");
+    let form = serde_urlencoded::to_string([("manifest", &manifest)]).unwrap();
+
+    let toml = post_manifest("application/toml", &manifest).await;
+    let posted = post_manifest("application/x-www-form-urlencoded", &form).await;
+
+    assert_eq!(toml, (200, "Toy car: 2\nTotal: 2\n".to_owned()));
+    assert_eq!(posted, toml);
+    assert_eq!(
+        post_manifest("application/x-www-form-urlencoded", "toml=x").await,
+        (415, "".to_owned())
+    );
+    assert_eq!(
+        post_manifest("application/x-www-form-urlencoded", "manifest=%5B%5B").await,
+        (400, "Invalid manifest".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_duplicate_items_merged() {
+    let body = r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 3
+
+[[package.metadata.orders]]
+item = "Gum"
+quantity = 1
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 5
+"#;
+
+    assert_eq!(
+        post_manifest("application/toml", body).await,
+        (200, "Toy: 8\nGum: 1\nTotal: 9\n".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_merged_quantity_overflow() {
+    let body = r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 4294967295
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 1
+"#;
+
+    assert_eq!(
+        post_manifest("application/toml", body).await,
+        (400, "Quantity overflow for item: Toy\n".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_toml_content_type_aliases() {
+    let manifest = single_order_manifest(r#""Toy""#, "3");
+
+    for content_type in [
+        "application/toml",
+        "application/vnd.cargo+toml",
+        "text/toml",
+    ] {
+        assert_eq!(
+            post_manifest(content_type, &manifest).await,
+            (200, "Toy: 3\nTotal: 3\n".to_owned())
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_order_lines_joined() {
+    let (status, single) =
+        post_manifest("application/toml", &single_order_manifest(r#""Toy""#, "3")).await;
+    assert_eq!(status, 200);
+    assert!(!single.starts_with('\n'));
+    assert_eq!(single.lines().collect::<Vec<_>>(), ["Toy: 3", "Total: 3"]);
+
+    let body = r#"
+[package]
+name = "not-a-gift-order"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy"
+quantity = 3
+
+[[package.metadata.orders]]
+item = "Gum"
+quantity = 1
+
+[[package.metadata.orders]]
+item = "Bow"
+quantity = 2
+"#;
+    let (status, multi) = post_manifest("application/toml", body).await;
+    assert_eq!(status, 200);
+    assert_eq!(multi, "Toy: 3\nGum: 1\nBow: 2\nTotal: 6\n");
+    assert!(!multi.contains("\n\n"));
+}
+
+#[tokio::test]
+async fn test_workspace_metadata_fallback() {
+    let workspace = r#"
+[workspace]
+members = ["elves"]
+
+[workspace.package]
+keywords = ["Christmas 2024"]
+
+[[workspace.metadata.orders]]
+item = "Toy"
+quantity = 3
+
+[[workspace.metadata.orders]]
+item = "Bad"
+quantity = "many"
+"#;
+    assert_eq!(
+        post_manifest("application/toml", workspace).await,
+        (200, "Toy: 3\nTotal: 3\n".to_owned())
+    );
+
+    // `[package]` wins, so its missing metadata is not filled in from the workspace
+    let both = format!("[package]\nname = \"x\"\nkeywords = [\"Christmas 2024\"]\n{workspace}");
+    assert_eq!(
+        post_manifest("application/toml", &both).await,
+        (204, "".to_owned())
+    );
+
+    let without_keyword = r#"
+[workspace]
+members = ["elves"]
+
+[[workspace.metadata.orders]]
+item = "Toy"
+quantity = 3
+"#;
+    assert_eq!(
+        post_manifest("application/toml", without_keyword).await,
+        (400, "Magic keyword not provided".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_no_package_section() {
+    for (content_type, body) in [
+        ("application/toml", "[dependencies]\nserde = \"1\"\n"),
+        ("application/json", r#"{"dependencies": {}}"#),
+    ] {
+        assert_eq!(
+            post_manifest(content_type, body).await,
+            (400, "No package section".to_owned())
+        );
+    }
+}
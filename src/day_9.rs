@@ -1,36 +1,32 @@
 use std::{
     ops::Div,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use axum::{
     body::Bytes,
+    extract::Query,
     http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
 use serde::{Deserialize, Serialize};
 
+/// Packs the bucket size into the low byte and the timestamp into the seven above it.
+/// Overwriting the timestamp's own low byte instead threw away up to 255 ms of it.
 fn encode_state(bucket_size: u8, timestamp_ms: u64) -> u64 {
-    let mut encoded = [0_u8; 8];
-    for (idx, byte) in timestamp_ms.to_le_bytes().into_iter().enumerate().skip(1) {
-        encoded[idx] = byte;
-    }
-    encoded[0] = bucket_size;
-    u64::from_le_bytes(encoded)
+    (timestamp_ms << 8) | u64::from(bucket_size)
 }
 
 fn decode_state(state: u64) -> (u8, u64) {
-    let mut bytes = state.to_le_bytes();
-    let bucket_size = bytes[0];
-    let timestamp_ms = {
-        bytes[0] = 0;
-        u64::from_le_bytes(bytes)
-    };
-    (bucket_size, timestamp_ms)
+    (state as u8, state >> 8)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 enum Measurement {
     Gallons(f32),
@@ -39,42 +35,138 @@ enum Measurement {
     Pints(f32),
 }
 
-static BUCKET_STATE: AtomicU64 = AtomicU64::new(0);
+/// `Measurement`'s variants as they appear in JSON
+const UNITS: [&str; 4] = ["gallons", "liters", "litres", "pints"];
+
+/// Tells an object naming no known unit, `{}` included, apart from any other bad body.
+fn parse_measurement(body: &[u8]) -> Result<Measurement, &'static str> {
+    let value: serde_json::Value = serde_json::from_slice(body).map_err(|_| "Invalid JSON\n")?;
+    if value
+        .as_object()
+        .is_some_and(|object| !UNITS.iter().any(|unit| object.contains_key(*unit)))
+    {
+        return Err("Unknown measurement unit\n");
+    }
+    serde_json::from_value(value).map_err(|_| "Invalid JSON\n")
+}
+
+/// Starts full and stamped with the time of first use, rather than at the Unix epoch
+static BUCKET_STATE: LazyLock<AtomicU64> = LazyLock::new(|| AtomicU64::new(initial_state()));
 const MAX_BUCKET_SIZE: u8 = 5;
 const REFILL_TIME_MS: u64 = 1_000;
 const SINGLE_WITHDRAWAL_MILK: u8 = 1;
 
-pub async fn milk(headers: HeaderMap, body: Bytes) -> Response {
-    let success_resp = || (StatusCode::OK, "Milk withdrawn\n");
-    let no_milk_resp = || (StatusCode::TOO_MANY_REQUESTS, "No milk available\n");
-    let bad_req = || (StatusCode::BAD_REQUEST);
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn initial_state() -> u64 {
+    encode_state(MAX_BUCKET_SIZE, now_ms())
+}
+
+/// Bucket size at `now`, counting the milk that trickled back in since the last withdrawal.
+fn current_size(state: u64, now: u64) -> u8 {
+    let (size, ts) = decode_state(state);
+    let refilled = now
+        .saturating_sub(ts)
+        .div(REFILL_TIME_MS)
+        .min(MAX_BUCKET_SIZE.into()) as u8;
+    (size + refilled).min(MAX_BUCKET_SIZE)
+}
 
+/// Takes a single milk out of `bucket`, returning how much it held beforehand, or `None` when
+/// it has run dry.
+fn withdraw(bucket: &AtomicU64) -> Option<u8> {
+    let mut size = 0;
     // calculate the amount of time between the last time we withdrew a single milk
-    let has_milk = BUCKET_STATE.fetch_update(Ordering::Release, Ordering::Acquire, |old_state| {
-        let (old_size, old_ts) = decode_state(old_state);
-
-        // calculate the amount of time between the last time we withdrew a single milk
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        let duration_since_last = now - old_ts;
-
-        let delta_to_refill = duration_since_last
-            .div(REFILL_TIME_MS)
-            .min(MAX_BUCKET_SIZE.into()) as u8;
-
-        if old_size == 0 && delta_to_refill == 0 {
-            return None;
+    bucket
+        .fetch_update(Ordering::Release, Ordering::Acquire, |old_state| {
+            let now = now_ms();
+            size = current_size(old_state, now);
+            if size == 0 {
+                return None;
+            }
+
+            Some(encode_state(
+                size.saturating_sub(SINGLE_WITHDRAWAL_MILK),
+                now,
+            ))
+        })
+        .ok()
+        .map(|_| size)
+}
+
+/// Counters behind `/9/stats`
+struct MilkStats {
+    rejected_requests: AtomicU64,
+    peak_bucket_size: AtomicU64,
+}
+
+static MILK_STATS: MilkStats = MilkStats::new();
+
+impl MilkStats {
+    const fn new() -> Self {
+        Self {
+            rejected_requests: AtomicU64::new(0),
+            peak_bucket_size: AtomicU64::new(0),
         }
-        let new_size = (old_size + (delta_to_refill))
-            .min(MAX_BUCKET_SIZE)
-            .saturating_sub(SINGLE_WITHDRAWAL_MILK);
+    }
 
-        Some(encode_state(new_size, now))
-    });
+    /// Takes what [`withdraw`] returned for a single `milk` request.
+    fn record(&self, withdrawn: Option<u8>) {
+        match withdrawn {
+            Some(size) => {
+                self.peak_bucket_size
+                    .fetch_max(size.into(), Ordering::Relaxed);
+            }
+            None => {
+                self.rejected_requests.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
 
-    if has_milk.is_err() {
+    fn snapshot(&self) -> Stats {
+        Stats {
+            rejected_requests: self.rejected_requests.load(Ordering::Relaxed),
+            peak_bucket_size: self.peak_bucket_size.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct Stats {
+    /// `milk` requests turned away with `429`
+    rejected_requests: u64,
+    /// Fullest the bucket has been when `milk` drew from it
+    peak_bucket_size: u64,
+}
+
+#[utoipa::path(get, path = "/9/stats", responses((status = 200, description = "Counters since startup", body = Stats)))]
+pub async fn stats() -> Json<Stats> {
+    Json(MILK_STATS.snapshot())
+}
+
+#[utoipa::path(
+    post,
+    path = "/9/milk",
+    request_body(content = Option<Measurement>, description = "Unit to convert, when sent as JSON"),
+    responses(
+        (status = 200, description = "Milk withdrawn, or the converted measurement", body = Measurement),
+        (status = 400, description = "Malformed JSON, unknown unit, or a value no `f32` can hold"),
+        (status = 429, description = "No milk available"),
+    )
+)]
+pub async fn milk(headers: HeaderMap, body: Bytes) -> Response {
+    let success_resp = || (StatusCode::OK, "Milk withdrawn\n");
+    let no_milk_resp = || (StatusCode::TOO_MANY_REQUESTS, "No milk available\n");
+    let bad_req = |message| (StatusCode::BAD_REQUEST, message);
+
+    let withdrawn = withdraw(&BUCKET_STATE);
+    MILK_STATS.record(withdrawn);
+    if withdrawn.is_none() {
         return no_milk_resp().into_response();
     }
 
@@ -89,14 +181,13 @@ pub async fn milk(headers: HeaderMap, body: Bytes) -> Response {
     if !is_json {
         return success_resp().into_response();
     }
-    let Ok(measurements) = serde_json::from_slice::<Measurement>(&body) else {
-        return bad_req().into_response();
+    let measurements = match parse_measurement(&body) {
+        Ok(measurements) => measurements,
+        Err(message) => return bad_req(message).into_response(),
     };
-    let new_measurement = match measurements {
-        Measurement::Gallons(val) => Measurement::Liters(val * 3.78541),
-        Measurement::Liters(val) => Measurement::Gallons(val * (1.0 / 3.78541)),
-        Measurement::Litres(val) => Measurement::Pints(val * 1.75975),
-        Measurement::Pints(val) => Measurement::Litres(val * (1.0 / 1.75975)),
+    let new_measurement = match convert(measurements) {
+        Ok(new_measurement) => new_measurement,
+        Err(message) => return bad_req(message).into_response(),
     };
     (
         StatusCode::OK,
@@ -105,14 +196,72 @@ pub async fn milk(headers: HeaderMap, body: Bytes) -> Response {
         .into_response()
 }
 
-pub async fn refill() -> Response {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
-    let new_state = encode_state(MAX_BUCKET_SIZE as u8, now);
-    BUCKET_STATE.swap(new_state, Ordering::AcqRel);
-    (StatusCode::OK,).into_response()
+/// Litres in a US gallon, exactly
+const GALLONS_TO_LITERS: f64 = 3.785_411_784;
+/// Litres in an imperial pint, exactly
+const PINTS_TO_LITRES: f64 = 0.568_261_25;
+
+/// JSON has no `NaN` or infinities, and an `f32` overflows to the latter on e.g. `1e40`.
+fn convert(measurement: Measurement) -> Result<Measurement, &'static str> {
+    // Worked out in `f64`, so only the final result is rounded to `f32`
+    let converted = match measurement {
+        Measurement::Gallons(val) => {
+            Measurement::Liters((f64::from(val) * GALLONS_TO_LITERS) as f32)
+        }
+        Measurement::Liters(val) => {
+            Measurement::Gallons((f64::from(val) / GALLONS_TO_LITERS) as f32)
+        }
+        Measurement::Litres(val) => Measurement::Pints((f64::from(val) / PINTS_TO_LITRES) as f32),
+        Measurement::Pints(val) => Measurement::Litres((f64::from(val) * PINTS_TO_LITRES) as f32),
+    };
+    let (Measurement::Gallons(val)
+    | Measurement::Liters(val)
+    | Measurement::Litres(val)
+    | Measurement::Pints(val)) = converted;
+    if !val.is_finite() {
+        return Err("Invalid measurement value\n");
+    }
+    Ok(converted)
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RefillQuery {
+    /// Milk to add, from 1 to 5; fills the bucket when absent
+    amount: Option<u8>,
+}
+
+#[derive(Debug, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct Bucket {
+    bucket_size: u8,
+    max: u8,
+}
+
+#[utoipa::path(post, path = "/9/refill", params(RefillQuery), responses((status = 200, description = "Bucket refilled", body = Bucket), (status = 400, description = "`amount` out of range")))]
+pub async fn refill(Query(query): Query<RefillQuery>) -> Response {
+    let amount = query.amount.unwrap_or(MAX_BUCKET_SIZE);
+    if !(1..=MAX_BUCKET_SIZE).contains(&amount) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("amount must be between 1 and {MAX_BUCKET_SIZE}\n"),
+        )
+            .into_response();
+    }
+
+    let now = now_ms();
+    let old_state = BUCKET_STATE
+        .fetch_update(Ordering::Release, Ordering::Acquire, |old_state| {
+            let size = current_size(old_state, now).saturating_add(amount);
+            Some(encode_state(size.min(MAX_BUCKET_SIZE), now))
+        })
+        .unwrap();
+    let bucket_size = (current_size(old_state, now) + amount).min(MAX_BUCKET_SIZE);
+
+    Json(Bucket {
+        bucket_size,
+        max: MAX_BUCKET_SIZE,
+    })
+    .into_response()
 }
 
 #[test]
@@ -125,3 +274,169 @@ fn test_encode_round_trip() {
     assert_eq!(bucket_size, d_bucket_size,);
     assert_eq!(timestamp_ms, d_timestamp_ms,);
 }
+
+#[test]
+fn test_encode_boundaries() {
+    assert_eq!(decode_state(encode_state(255, 0)), (255, 0));
+    assert_eq!(
+        decode_state(encode_state(0, u64::MAX >> 8)),
+        (0, u64::MAX >> 8)
+    );
+
+    // Timestamps apart only in their lowest byte must neither collide nor leak into the bucket
+    let (a, b) = (0x1234_5600, 0x1234_56ff);
+    assert_ne!(encode_state(1, a), encode_state(1, b));
+    assert_eq!(decode_state(encode_state(1, a)), (1, a));
+    assert_eq!(decode_state(encode_state(1, b)), (1, b));
+}
+
+#[test]
+fn test_initial_state_is_full() {
+    let before = now_ms();
+    let (size, ts) = decode_state(initial_state());
+    assert_eq!(size, MAX_BUCKET_SIZE);
+    assert!((before..=now_ms()).contains(&ts));
+
+    // A cold bucket hands out exactly its capacity before running dry
+    let cold = AtomicU64::new(initial_state());
+    assert!((0..MAX_BUCKET_SIZE).all(|_| withdraw(&cold).is_some()));
+    assert_eq!(withdraw(&cold), None);
+}
+
+#[test]
+fn test_parse_measurement_errors() {
+    assert!(matches!(
+        parse_measurement(br#"{"liters": 2.0}"#),
+        Ok(Measurement::Liters(2.0))
+    ));
+    for body in [&br#"{}"#[..], br#"{"teaspoons": 5.0}"#] {
+        assert_eq!(
+            parse_measurement(body).err(),
+            Some("Unknown measurement unit\n")
+        );
+    }
+    for body in [&br#"{"liters": "#[..], b"milk", br#"{"liters": "two"}"#] {
+        assert_eq!(parse_measurement(body).err(), Some("Invalid JSON\n"));
+    }
+}
+
+#[test]
+fn test_convert_rejects_non_finite() {
+    let converted = |body: &str| parse_measurement(body.as_bytes()).and_then(convert);
+
+    assert!(matches!(
+        converted(r#"{"liters": 3.78541}"#),
+        Ok(Measurement::Gallons(val)) if (val - 1.0).abs() < 1e-6
+    ));
+    // `1e40` overflows the `f32` on parsing; `3e38` only once converted
+    for body in [
+        r#"{"gallons": 1e40}"#,
+        r#"{"gallons": 3e38}"#,
+        r#"{"pints": -1e39}"#,
+    ] {
+        assert_eq!(
+            converted(body).err(),
+            Some("Invalid measurement value\n"),
+            "{body}"
+        );
+    }
+    assert_eq!(
+        convert(Measurement::Litres(f32::NAN)).err(),
+        Some("Invalid measurement value\n")
+    );
+}
+
+#[test]
+fn test_conversion_round_trip() {
+    let Ok(Measurement::Liters(liters)) = convert(Measurement::Gallons(1.0)) else {
+        panic!("gallons convert to liters");
+    };
+    let Ok(Measurement::Gallons(gallons)) = convert(Measurement::Liters(liters)) else {
+        panic!("liters convert to gallons");
+    };
+    assert!((gallons - 1.0).abs() < 1e-6, "{gallons}");
+
+    let Ok(Measurement::Pints(pints)) = convert(Measurement::Litres(PINTS_TO_LITRES as f32)) else {
+        panic!("litres convert to pints");
+    };
+    assert!((pints - 1.0).abs() < 1e-6, "{pints}");
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_encode_round_trip_any(bucket_size: u8, timestamp_ms: u64) {
+        proptest::prop_assert_eq!(
+            decode_state(encode_state(bucket_size, timestamp_ms)),
+            (bucket_size, timestamp_ms & 0x00FF_FFFF_FFFF_FFFF)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_partial_refill() {
+    let refill_by = |amount| async move {
+        let res = refill(Query(RefillQuery { amount })).await;
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice::<serde_json::Value>(&body).ok(),
+        )
+    };
+    let bucket = |size: u8| {
+        (
+            StatusCode::OK,
+            Some(serde_json::json!({"bucket_size": size, "max": MAX_BUCKET_SIZE})),
+        )
+    };
+
+    BUCKET_STATE.store(encode_state(0, now_ms()), Ordering::Release);
+    assert_eq!(refill_by(Some(1)).await, bucket(1));
+    assert_eq!(refill_by(Some(2)).await, bucket(3));
+    assert_eq!(refill_by(Some(2)).await, bucket(5));
+    assert_eq!(refill_by(Some(1)).await, bucket(5));
+
+    BUCKET_STATE.store(encode_state(0, now_ms()), Ordering::Release);
+    assert_eq!(
+        refill_by(Some(MAX_BUCKET_SIZE)).await,
+        bucket(MAX_BUCKET_SIZE)
+    );
+    assert_eq!(refill_by(None).await, bucket(MAX_BUCKET_SIZE));
+
+    for amount in [0, MAX_BUCKET_SIZE + 1] {
+        assert_eq!(
+            refill_by(Some(amount)).await,
+            (StatusCode::BAD_REQUEST, None)
+        );
+    }
+}
+
+#[test]
+fn test_milk_stats() {
+    let bucket = AtomicU64::new(encode_state(3, now_ms()));
+    let stats = MilkStats::new();
+
+    for _ in 0..4 {
+        stats.record(withdraw(&bucket));
+    }
+
+    assert_eq!(
+        stats.snapshot(),
+        Stats {
+            rejected_requests: 1,
+            peak_bucket_size: 3,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_stats_shape() {
+    let Json(stats) = stats().await;
+    let stats = serde_json::to_value(stats).unwrap();
+
+    assert!(stats["rejected_requests"].is_u64());
+    assert!(stats["peak_bucket_size"].as_u64().unwrap() <= MAX_BUCKET_SIZE.into());
+}
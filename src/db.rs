@@ -0,0 +1,206 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+#[derive(Debug, PartialEq)]
+pub struct PoolConfig {
+    max_connections: u32,
+    min_connections: u32,
+    acquire_timeout: Duration,
+    idle_timeout: Duration,
+}
+
+/// Reads `name` as a `T`, warning and falling back to `default` when it isn't one.
+fn parse_var<T: FromStr + Display>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    default: T,
+) -> T {
+    let Some(value) = var(name) else {
+        return default;
+    };
+    value.trim().parse().unwrap_or_else(|_| {
+        tracing::warn!(%value, %default, "{name} is not a valid number, using the default");
+        default
+    })
+}
+
+impl PoolConfig {
+    /// Reads `MAX_DB_CONNECTIONS` (default 10), `MIN_DB_CONNECTIONS` (default 2),
+    /// `DB_ACQUIRE_TIMEOUT_MS` (default 3000) and `DB_IDLE_TIMEOUT_SECS` (default 600)
+    pub fn from_env() -> Self {
+        Self::new(|name| std::env::var(name).ok())
+    }
+
+    fn new(var: impl Fn(&str) -> Option<String>) -> Self {
+        // A pool of zero connections could never serve a query
+        let max_connections = match parse_var(&var, "MAX_DB_CONNECTIONS", 10u32) {
+            0 => {
+                tracing::warn!("MAX_DB_CONNECTIONS is 0, using 1");
+                1
+            }
+            max => max,
+        };
+
+        Self {
+            max_connections,
+            min_connections: parse_var(&var, "MIN_DB_CONNECTIONS", 2),
+            acquire_timeout: Duration::from_millis(parse_var(&var, "DB_ACQUIRE_TIMEOUT_MS", 3000)),
+            idle_timeout: Duration::from_secs(parse_var(&var, "DB_IDLE_TIMEOUT_SECS", 600)),
+        }
+    }
+
+    /// sqlx fixes a pool's options at construction, so rather than tuning the pool Shuttle
+    /// hands us, this opens a new one against the same database and closes the original.
+    pub async fn apply(&self, pool: PgPool) -> Result<PgPool, sqlx::Error> {
+        let options = (*pool.connect_options()).clone();
+        pool.close().await;
+
+        tracing::info!(
+            max_connections = self.max_connections,
+            min_connections = self.min_connections,
+            acquire_timeout = ?self.acquire_timeout,
+            idle_timeout = ?self.idle_timeout,
+            "database pool"
+        );
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections.min(self.max_connections))
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .connect_with(options)
+            .await
+    }
+}
+
+#[test]
+fn test_pool_config_defaults_and_overrides() {
+    assert_eq!(
+        PoolConfig::new(|_| None),
+        PoolConfig {
+            max_connections: 10,
+            min_connections: 2,
+            acquire_timeout: Duration::from_millis(3000),
+            idle_timeout: Duration::from_secs(600),
+        }
+    );
+
+    let config = PoolConfig::new(|name| match name {
+        "MAX_DB_CONNECTIONS" => Some("4".to_owned()),
+        "DB_IDLE_TIMEOUT_SECS" => Some("not a number".to_owned()),
+        _ => None,
+    });
+    assert_eq!(config.max_connections, 4);
+    assert_eq!(config.idle_timeout, Duration::from_secs(600));
+}
+
+#[test]
+fn test_pool_config_connection_bounds() {
+    let max_connections = |value: &'static str| {
+        PoolConfig::new(|name| (name == "MAX_DB_CONNECTIONS").then(|| value.to_owned()))
+            .max_connections
+    };
+
+    assert_eq!(max_connections("0"), 1);
+    // Out of `u32` range, so rejected rather than truncated to 0
+    assert_eq!(max_connections("4294967296"), 10);
+    assert_eq!(max_connections("-1"), 10);
+    assert_eq!(max_connections("4294967295"), u32::MAX);
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_small_pool_serves_concurrent_queries() {
+    let url = std::env::var("DATABASE_URL").unwrap();
+    let config = PoolConfig::new(|name| (name == "MAX_DB_CONNECTIONS").then(|| "2".to_owned()));
+    let pool = config
+        .apply(PgPool::connect(&url).await.unwrap())
+        .await
+        .unwrap();
+
+    let queries = (0..5).map(|_| {
+        let pool = pool.clone();
+        tokio::spawn(async move { sqlx::query("SELECT pg_sleep(0.1)").execute(&pool).await })
+    });
+
+    for query in queries.collect::<Vec<_>>() {
+        query.await.unwrap().unwrap();
+    }
+    assert!(pool.size() <= 2);
+}
+
+/// An empty database created next to the one at `DATABASE_URL`, for tests that need a
+/// known starting point.
+#[cfg(test)]
+pub struct TestDatabase {
+    pub pool: PgPool,
+    admin: PgPool,
+    name: String,
+}
+
+#[cfg(test)]
+impl TestDatabase {
+    pub async fn create() -> Self {
+        let admin = PgPool::connect(&std::env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let name = format!("test_{}", sqlx::types::Uuid::new_v4().simple());
+        sqlx::query(&format!("CREATE DATABASE {name}"))
+            .execute(&admin)
+            .await
+            .unwrap();
+        let options = admin.connect_options().as_ref().clone().database(&name);
+        let pool = PgPool::connect_with(options).await.unwrap();
+        Self { pool, admin, name }
+    }
+
+    /// Drops the database; left behind if the test panics before getting here.
+    pub async fn remove(self) {
+        self.pool.close().await;
+        sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .execute(&self.admin)
+            .await
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+async fn schema(pool: &PgPool) -> Vec<String> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT format('%s.%s %s %s %s', table_name, column_name, data_type, is_nullable, column_default)
+        FROM information_schema.columns
+        WHERE table_schema = 'public' AND table_name <> '_sqlx_migrations'
+        UNION ALL
+        SELECT indexdef FROM pg_indexes WHERE schemaname = 'public' AND tablename <> '_sqlx_migrations'
+        UNION ALL
+        SELECT format('%s %s %s', event_object_table, trigger_name, action_statement)
+        FROM information_schema.triggers
+        ORDER BY 1
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    rows.into_iter().map(|(row,)| row).collect()
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_migrations_revert_cleanly() {
+    // Reverting drops every table, so work in a database of our own
+    let db = TestDatabase::create().await;
+    let migrator = sqlx::migrate!();
+
+    migrator.run(&db.pool).await.unwrap();
+    let migrated = schema(&db.pool).await;
+    migrator.undo(&db.pool, 0).await.unwrap();
+    let reverted = schema(&db.pool).await;
+    migrator.run(&db.pool).await.unwrap();
+    let remigrated = schema(&db.pool).await;
+
+    db.remove().await;
+    assert!(migrated.iter().any(|row| row.starts_with("quotes.id ")));
+    assert_eq!(reverted, Vec::<String>::new());
+    assert_eq!(remigrated, migrated);
+}
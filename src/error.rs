@@ -0,0 +1,179 @@
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sqlx::types::Uuid;
+
+/// Handler errors, rendered as a JSON:API error document.
+#[derive(Debug, PartialEq)]
+pub enum AppError {
+    NotFound,
+    /// A lookup by id matched nothing; the id is echoed back in the error's `meta`
+    MissingId(&'static str, Uuid),
+    BadRequest(String),
+    /// An `If-Match` precondition failed; carries the version the resource is actually at
+    VersionConflict(i32),
+    /// A write broke the named `CHECK` constraint, which is echoed back in the error's `meta`
+    ConstraintViolation(String),
+    /// A token is signed with an algorithm the key can't verify; the `alg` goes in the `meta`
+    UnsupportedAlgorithm(String),
+    ServiceUnavailable,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    /// The body is well-formed but breaks these rules; each one gets its own error object
+    Unprocessable(Vec<String>),
+    /// An extractor rejected the request; keeps the extractor's own status
+    Rejected(StatusCode, String),
+}
+
+#[derive(Serialize)]
+struct ErrorObject {
+    status: String,
+    title: &'static str,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<ErrorMeta>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ErrorMeta {
+    Id { id: Uuid },
+    Version { current_version: i32 },
+    Constraint { constraint: String },
+    Algorithm { alg: String },
+}
+
+#[derive(Serialize)]
+struct ErrorDocument {
+    errors: Vec<ErrorObject>,
+}
+
+impl AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound | AppError::MissingId(..) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_)
+            | AppError::ConstraintViolation(_)
+            | AppError::UnsupportedAlgorithm(_) => StatusCode::BAD_REQUEST,
+            AppError::VersionConflict(_) => StatusCode::CONFLICT,
+            AppError::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Rejected(status, _) => *status,
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            AppError::NotFound => "The requested resource does not exist".to_owned(),
+            AppError::MissingId(detail, _) => (*detail).to_owned(),
+            AppError::BadRequest(detail) | AppError::Rejected(_, detail) => detail.clone(),
+            AppError::VersionConflict(_) => "version_conflict".to_owned(),
+            AppError::ConstraintViolation(_) => "constraint_violation".to_owned(),
+            AppError::UnsupportedAlgorithm(_) => "unsupported_algorithm".to_owned(),
+            AppError::ServiceUnavailable => "The database is unavailable".to_owned(),
+            AppError::PayloadTooLarge => "The request has too many entries".to_owned(),
+            AppError::UnsupportedMediaType => "The requested format is not supported".to_owned(),
+            AppError::Unprocessable(violations) => violations.join("; "),
+        }
+    }
+
+    fn meta(&self) -> Option<ErrorMeta> {
+        match self {
+            AppError::MissingId(_, id) => Some(ErrorMeta::Id { id: *id }),
+            AppError::VersionConflict(current_version) => Some(ErrorMeta::Version {
+                current_version: *current_version,
+            }),
+            AppError::ConstraintViolation(constraint) => Some(ErrorMeta::Constraint {
+                constraint: constraint.clone(),
+            }),
+            AppError::UnsupportedAlgorithm(alg) => Some(ErrorMeta::Algorithm { alg: alg.clone() }),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let object = |detail, meta| ErrorObject {
+            status: status.as_str().to_owned(),
+            title: status.canonical_reason().unwrap_or_default(),
+            detail,
+            meta,
+        };
+        let errors = match self {
+            AppError::Unprocessable(violations) => violations
+                .into_iter()
+                .map(|detail| object(detail, None))
+                .collect(),
+            _ => vec![object(self.detail(), self.meta())],
+        };
+        let document = ErrorDocument { errors };
+
+        (
+            status,
+            [(CONTENT_TYPE, "application/vnd.api+json")],
+            serde_json::to_string(&document).unwrap(),
+        )
+            .into_response()
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        AppError::Rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::Rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        AppError::Rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+/// `axum::Json` whose rejections are reported as an [`AppError`].
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct AppJson<T>(pub T);
+
+/// `axum::extract::Query` whose rejections are reported as an [`AppError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct AppQuery<T>(pub T);
+
+/// `axum::extract::Path` whose rejections are reported as an [`AppError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct AppPath<T>(pub T);
+
+#[tokio::test]
+async fn test_app_error_document() {
+    let res = AppError::BadRequest("Invalid quote id".to_owned()).into_response();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/vnd.api+json");
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "errors": [{"status": "400", "title": "Bad Request", "detail": "Invalid quote id"}]
+        })
+    );
+}
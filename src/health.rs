@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use sqlx::PgPool;
+
+const DB_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Debug, PartialEq, utoipa::ToSchema)]
+pub struct Health {
+    status: &'static str,
+    db: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[utoipa::path(get, path = "/healthz", responses((status = 200, body = Health), (status = 503, description = "Database unreachable", body = Health)))]
+pub async fn healthz(State(pool): State<PgPool>) -> (StatusCode, Json<Health>) {
+    let check = tokio::time::timeout(DB_TIMEOUT, sqlx::query("SELECT 1").execute(&pool)).await;
+
+    let error = match check {
+        Ok(Ok(_)) => {
+            return (
+                StatusCode::OK,
+                Json(Health {
+                    status: "ok",
+                    db: "up",
+                    error: None,
+                }),
+            )
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => format!("database did not respond within {DB_TIMEOUT:?}"),
+    };
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(Health {
+            status: "degraded",
+            db: "down",
+            error: Some(error),
+        }),
+    )
+}
+
+#[tokio::test]
+async fn test_healthz_reports_unreachable_db() {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(100))
+        .connect_lazy("postgres://localhost:1/unused")
+        .unwrap();
+
+    let (status, Json(health)) = healthz(State(pool)).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!((health.status, health.db), ("degraded", "down"));
+    assert!(health.error.is_some());
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_healthz_reports_healthy_db() {
+    let db = crate::db::TestDatabase::create().await;
+
+    let (status, Json(health)) = healthz(State(db.pool.clone())).await;
+
+    db.remove().await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        serde_json::to_value(health).unwrap(),
+        serde_json::json!({"status": "ok", "db": "up"})
+    );
+}
+
+#[tokio::test]
+async fn test_healthz_route() {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(100))
+        .connect_lazy("postgres://localhost:1/unused")
+        .unwrap();
+    let router = Router::new()
+        .route("/healthz", get(healthz))
+        .with_state(pool);
+
+    let res = router
+        .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        (&body["status"], &body["db"]),
+        (&"degraded".into(), &"down".into())
+    );
+}
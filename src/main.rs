@@ -1,19 +1,18 @@
 #![feature(portable_simd)]
 
-use std::{
-    sync::{Arc, Mutex},
-    time::Duration,
-};
+use std::sync::{Arc, Mutex};
 
 use axum::{
-    body::Body,
-    http::{Request, Response},
+    error_handling::HandleErrorLayer,
+    extract::DefaultBodyLimit,
+    response::Html,
     routing::{delete, get, post, put},
-    Router,
+    Json, Router,
 };
 use rand::SeedableRng;
-use tower_http::{services::ServeDir, trace::TraceLayer};
-use tracing::Span;
+use tower::ServiceBuilder;
+use tower_http::{limit::RequestBodyLimitLayer, services::ServeDir, trace::TraceLayer};
+use utoipa::OpenApi;
 
 mod day_1;
 mod day_12;
@@ -23,57 +22,220 @@ mod day_2;
 mod day_23;
 mod day_5;
 mod day_9;
+mod db;
+mod error;
+mod health;
+mod metrics;
+mod middleware;
+mod shutdown;
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    day_1::hello_world,
+    day_1::seek,
+    day_2::dest,
+    day_2::key,
+    day_2::v6_dest,
+    day_2::v6_key,
+    day_2::v6_add_dest,
+    day_2::v6_add_key,
+    day_2::cidr,
+    day_2::classify,
+    day_2::batch_dest,
+    day_2::batch_key,
+    day_2::ip2int,
+    day_2::int2ip,
+    day_2::overlap,
+    day_2::next_ip,
+    day_2::prev_ip,
+    day_2::multicast_valid,
+    day_5::manifest,
+    day_9::milk,
+    day_9::refill,
+    day_9::stats,
+    day_12::board,
+    day_12::reset,
+    day_12::place,
+    day_12::random_board,
+    day_12::history,
+    day_16::wrap,
+    day_16::unwrap,
+    day_16::decode,
+    day_16::cookie_payload,
+    day_19::reset,
+    day_19::cite,
+    day_19::amend,
+    day_19::history,
+    day_19::history_version,
+    day_19::remove,
+    day_19::undo,
+    day_19::restore,
+    day_19::draft,
+    day_19::bulk,
+    day_19::list,
+    day_19::authors,
+    day_19::author_quotes,
+    day_19::search,
+    day_19::trash,
+    day_19::random,
+    day_19::export,
+    day_23::star,
+    day_23::colour_present,
+    day_23::ornament,
+    day_23::events,
+    day_23::tree,
+    day_23::lockfile,
+    health::healthz,
+    metrics::metrics,
+))]
+struct ApiDoc;
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn docs() -> Html<&'static str> {
+    Html(include_str!("../assets/docs.html"))
+}
 
 #[shuttle_runtime::main]
-async fn main(#[shuttle_shared_db::Postgres] pool: sqlx::PgPool) -> shuttle_axum::ShuttleAxum {
+async fn main(
+    #[shuttle_shared_db::Postgres] pool: sqlx::PgPool,
+) -> Result<shutdown::GracefulAxum, shuttle_runtime::Error> {
+    let pool = db::PoolConfig::from_env()
+        .apply(pool)
+        .await
+        .expect("Failed to configure the database pool");
     sqlx::migrate!()
         .run(&pool)
         .await
         .expect("Failed to run migrations");
 
-    let std_rng = rand::rngs::StdRng::seed_from_u64(2024);
+    let rng = Arc::new(Mutex::new(rand::rngs::StdRng::seed_from_u64(2024)));
+    let cors = Arc::new(middleware::cors::CorsConfig::from_env());
+
+    // There is no `/16/revoke` yet; it belongs here once it exists
+    let admin = Router::new()
+        .route("/9/refill", post(day_9::refill))
+        .route("/12/reset", post(day_12::reset))
+        .with_state(rng.clone())
+        .route("/19/reset", post(day_19::reset))
+        .route_layer(axum::middleware::from_fn_with_state(
+            middleware::auth::AdminKey::from_env(),
+            middleware::auth::require_api_key,
+        ));
 
     let router = Router::new()
         .route("/", get(day_1::hello_world))
-        .route("/-1/seek", get(day_1::seek))
+        .route(
+            "/-1/seek",
+            get(day_1::seek).with_state(day_1::SeekUrl::from_env()),
+        )
         .route("/2/dest", get(day_2::dest))
         .route("/2/key", get(day_2::key))
         .route("/2/v6/dest", get(day_2::v6_dest))
         .route("/2/v6/key", get(day_2::v6_key))
-        .route("/5/manifest", post(day_5::manifest))
+        .route("/2/v6/add/dest", get(day_2::v6_add_dest))
+        .route("/2/v6/add/key", get(day_2::v6_add_key))
+        .route("/2/cidr", get(day_2::cidr))
+        .route("/2/classify", get(day_2::classify))
+        .route("/2/batch/dest", post(day_2::batch_dest))
+        .route("/2/batch/key", post(day_2::batch_key))
+        .route("/2/ip2int", get(day_2::ip2int))
+        .route("/2/int2ip", get(day_2::int2ip))
+        .route("/2/overlap", post(day_2::overlap))
+        .route("/2/next", get(day_2::next_ip))
+        .route("/2/prev", get(day_2::prev_ip))
+        .route("/2/multicast/valid", get(day_2::multicast_valid))
+        .route(
+            "/5/manifest",
+            post(day_5::manifest)
+                .layer(RequestBodyLimitLayer::new(day_5::MAX_MANIFEST_BYTES))
+                .layer(axum::middleware::map_response(day_5::manifest_too_large)),
+        )
         .route("/9/milk", post(day_9::milk))
-        .route("/9/refill", post(day_9::refill))
+        .route("/9/stats", get(day_9::stats))
         .route("/12/board", get(day_12::board))
-        .route("/12/reset", post(day_12::reset))
         .route("/12/place/:team/:column", post(day_12::place))
         .route("/12/random-board", get(day_12::random_board))
-        .with_state(Arc::new(Mutex::new(std_rng)))
+        .route("/12/history", get(day_12::history))
+        .with_state(rng.clone())
         .route("/16/wrap", post(day_16::wrap))
         .route("/16/unwrap", get(day_16::unwrap))
         .route("/16/decode", post(day_16::decode))
-        .route("/19/reset", post(day_19::reset))
-        .route("/19/cite/:id", get(day_19::cite))
+        .route("/16/cookie-payload", get(day_16::cookie_payload))
+        .route("/19/cite/:id", get(day_19::cite).patch(day_19::amend))
+        .route("/19/cite/:id/history", get(day_19::history))
+        .route(
+            "/19/cite/:id/history/:version",
+            get(day_19::history_version),
+        )
         .route("/19/remove/:id", delete(day_19::remove))
         .route("/19/undo/:id", put(day_19::undo))
+        .route("/19/restore/:id", put(day_19::restore))
         .route("/19/draft", post(day_19::draft))
+        .route("/19/bulk", post(day_19::bulk))
         .route("/19/list", get(day_19::list))
-        .route("/23/star", get(day_23::star))
-        .route("/23/present/:c", get(day_23::colour_present))
-        .route("/23/ornament/:state/:n", get(day_23::ornament))
-        .route("/23/lockfile", post(day_23::lockfile))
+        .route("/19/authors", get(day_19::authors))
+        .route("/19/authors/:author", get(day_19::author_quotes))
+        .route("/19/search", get(day_19::search))
+        .route("/19/trash", get(day_19::trash))
+        .route("/19/random", get(day_19::random))
+        .route("/19/export", get(day_19::export))
+        .nest("/23", day_23::router())
+        .route("/openapi.json", get(openapi))
+        .route("/docs", get(docs))
         .nest_service("/assets", ServeDir::new("assets"))
-        .with_state(pool)
-        .layer(TraceLayer::new_for_http().make_span_with(|req: &Request<Body>| {
-            tracing::info_span!("", method = %req.method(), uri = %req.uri())
-        }).on_response(|res: &Response<Body>, latency: Duration, _span: &Span| {
-            if res.status().is_server_error() {
-                tracing::error!(status = %res.status().as_u16(), latency = ?latency);
-            } else if res.status().is_client_error() {
-                tracing::warn!(status = %res.status().as_u16(), latency = ?latency);
-            } else {
-                tracing::info!(status = %res.status().as_u16(), latency = ?latency);
-            }
-        }).on_failure(()));
+        .merge(admin)
+        .route_layer(axum::middleware::from_fn(metrics::track))
+        .with_state(pool.clone())
+        .layer(cors.layer())
+        .layer(axum::middleware::from_fn_with_state(
+            cors,
+            middleware::cors::reject_invalid_preflight,
+        ))
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(middleware::timeout::handle_timeout))
+                .timeout(middleware::timeout::REQUEST_TIMEOUT),
+        )
+        .layer(axum::middleware::from_fn(
+            middleware::request_id::request_id,
+        ))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(middleware::trace::make_span)
+                .on_response(middleware::trace::on_response)
+                .on_failure(()),
+        )
+        .layer(middleware::compression::compression())
+        // Registered after the trace, metrics and compression layers so that probes and scrapes
+        // don't flood the logs or count themselves, and Prometheus gets plain text. Skipping the
+        // timeout and request-id layers too is intended: `healthz` bounds its own query, and
+        // nobody correlates probe requests
+        .route("/healthz", get(health::healthz).with_state(pool))
+        .route("/metrics", get(metrics::metrics))
+        // Outermost so it covers every route; `/5/manifest` layers a tighter limit of its own.
+        // axum's own 2 MB default would otherwise kick in first for buffering extractors
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(
+            middleware::body_limit::MAX_BODY_BYTES,
+        ))
+        .layer(axum::middleware::map_response(
+            middleware::body_limit::body_too_large,
+        ));
+
+    Ok(shutdown::GracefulAxum(router))
+}
+
+#[tokio::test]
+async fn test_openapi_spec() {
+    let Json(spec) = openapi().await;
+    let spec = serde_json::to_value(spec).unwrap();
 
-    Ok(router.into())
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+    let paths = spec["paths"].as_object().unwrap();
+    assert!(!paths.is_empty());
+    assert!(paths.contains_key("/19/cite/{id}"));
+    assert!(paths["/12/place/{team}/{column}"]["post"]["parameters"].is_array());
 }
@@ -0,0 +1,111 @@
+use std::{sync::LazyLock, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    exponential_buckets, CounterVec, Encoder, HistogramOpts, HistogramVec, Opts, Registry,
+    TextEncoder,
+};
+
+struct Metrics {
+    registry: Registry,
+    requests: CounterVec,
+    latency: HistogramVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new();
+    let requests = CounterVec::new(
+        Opts::new("http_requests_total", "Number of HTTP requests handled"),
+        &["method", "route", "status"],
+    )
+    .unwrap();
+    let latency = HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_ms",
+            "HTTP request latency in milliseconds",
+        )
+        .buckets(exponential_buckets(1.0, 2.0, 14).unwrap()),
+        &["route", "status"],
+    )
+    .unwrap();
+    registry.register(Box::new(requests.clone())).unwrap();
+    registry.register(Box::new(latency.clone())).unwrap();
+
+    Metrics {
+        registry,
+        requests,
+        latency,
+    }
+});
+
+/// Records a request against its route template rather than the raw URI, so `/19/cite/:id`
+/// is a single series. Installed with `route_layer` so that `MatchedPath` is available.
+pub async fn track(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| req.uri().path().to_owned());
+    let start = Instant::now();
+
+    let res = next.run(req).await;
+
+    let status = res.status().as_u16().to_string();
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    METRICS
+        .requests
+        .with_label_values(&[&method, &route, &status])
+        .inc();
+    METRICS
+        .latency
+        .with_label_values(&[&route, &status])
+        .observe(latency_ms);
+
+    res
+}
+
+#[utoipa::path(get, path = "/metrics", responses((status = 200, description = "Prometheus text exposition", content_type = "text/plain", body = String)))]
+pub async fn metrics() -> Response {
+    let mut buffer = Vec::new();
+    if TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .is_err()
+    {
+        return (StatusCode::INTERNAL_SERVER_ERROR,).into_response();
+    }
+
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        String::from_utf8(buffer).unwrap(),
+    )
+        .into_response()
+}
+
+#[tokio::test]
+async fn test_track_counts_requests() {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    let router = Router::new()
+        .route("/metrics-test/:id", get(|| async { "ok" }))
+        .route_layer(axum::middleware::from_fn(track));
+    router
+        .oneshot(Request::get("/metrics-test/1").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let res = metrics().await;
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(body
+        .contains(r#"http_requests_total{method="GET",route="/metrics-test/:id",status="200"} 1"#));
+}
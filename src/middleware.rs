@@ -0,0 +1,7 @@
+pub mod auth;
+pub mod body_limit;
+pub mod compression;
+pub mod cors;
+pub mod request_id;
+pub mod timeout;
+pub mod trace;
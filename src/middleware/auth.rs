@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+static X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Key required on admin routes. `None` turns the check off.
+#[derive(Debug, Clone)]
+pub struct AdminKey(Option<Arc<str>>);
+
+impl AdminKey {
+    /// Reads `ADMIN_API_KEY`; leaving it unset disables admin authentication
+    pub fn from_env() -> Self {
+        Self(std::env::var("ADMIN_API_KEY").ok().map(Into::into))
+    }
+}
+
+pub async fn require_api_key(
+    State(AdminKey(expected)): State<AdminKey>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = expected else {
+        return next.run(req).await;
+    };
+
+    match req.headers().get(&X_API_KEY) {
+        None => (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "missing_api_key" })),
+        )
+            .into_response(),
+        Some(key) if key.as_bytes() != expected.as_bytes() => (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "invalid_api_key" })),
+        )
+            .into_response(),
+        Some(_) => next.run(req).await,
+    }
+}
+
+#[cfg(test)]
+async fn send(key: AdminKey, header: Option<&'static str>) -> (StatusCode, String) {
+    use axum::{body::Body, routing::post, Router};
+    use tower::ServiceExt;
+
+    let router = Router::new()
+        .route("/admin", post(|| async { "ok" }))
+        .route_layer(axum::middleware::from_fn_with_state(key, require_api_key));
+    let mut req = Request::post("/admin");
+    if let Some(header) = header {
+        req = req.header(&X_API_KEY, header);
+    }
+
+    let res = router
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_api_key_missing() {
+    let (status, body) = send(AdminKey(Some("secret".into())), None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body, r#"{"error":"missing_api_key"}"#);
+}
+
+#[tokio::test]
+async fn test_api_key_invalid() {
+    let (status, body) = send(AdminKey(Some("secret".into())), Some("guess")).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body, r#"{"error":"invalid_api_key"}"#);
+}
+
+#[tokio::test]
+async fn test_api_key_valid() {
+    let (status, body) = send(AdminKey(Some("secret".into())), Some("secret")).await;
+    assert_eq!((status, &*body), (StatusCode::OK, "ok"));
+
+    let (status, body) = send(AdminKey(None), None).await;
+    assert_eq!((status, &*body), (StatusCode::OK, "ok"));
+}
@@ -0,0 +1,79 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, StatusCode},
+    response::Response,
+};
+
+pub const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Replaces the `413` body produced by the limit layer, or by an extractor tripping over it
+/// mid-stream, with a readable message. Both tag their rejection as `text/plain`, unlike the
+/// bare `413`s handlers return themselves, which get left alone.
+pub async fn body_too_large(res: Response) -> Response {
+    let from_limit = res.headers().get(header::CONTENT_TYPE)
+        == Some(&HeaderValue::from_static("text/plain; charset=utf-8"));
+    if res.status() != StatusCode::PAYLOAD_TOO_LARGE || !from_limit {
+        return res;
+    }
+    Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .body(Body::new("Request body too large\n".to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+async fn post(uri: &str, body: Vec<u8>, content_type: &str) -> (StatusCode, String) {
+    use axum::{body::Bytes, http::Request, routing, Router};
+    use tower::ServiceExt;
+    use tower_http::limit::RequestBodyLimitLayer;
+
+    let router = Router::new()
+        .route(
+            "/echo",
+            routing::post(|body: Bytes| async move { body.len().to_string() }),
+        )
+        .route(
+            "/5/manifest",
+            routing::post(crate::day_5::manifest)
+                .layer(RequestBodyLimitLayer::new(crate::day_5::MAX_MANIFEST_BYTES))
+                .layer(axum::middleware::map_response(
+                    crate::day_5::manifest_too_large,
+                )),
+        )
+        .layer(axum::extract::DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(MAX_BODY_BYTES))
+        .layer(axum::middleware::map_response(body_too_large));
+    let req = Request::post(uri)
+        .header("Content-Type", content_type)
+        .body(Body::from(body))
+        .unwrap();
+
+    let res = router.oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_global_body_limit() {
+    let (status, body) = post("/echo", vec![0; MAX_BODY_BYTES - 1], "text/plain").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, (MAX_BODY_BYTES - 1).to_string());
+
+    let (status, body) = post("/echo", vec![0; MAX_BODY_BYTES + 1], "text/plain").await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body, "Request body too large\n");
+}
+
+#[tokio::test]
+async fn test_manifest_body_limit_overrides_global() {
+    let limit = crate::day_5::MAX_MANIFEST_BYTES;
+    let (status, body) = post("/5/manifest", vec![b'#'; limit + 1], "application/toml").await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body, "Manifest too large\n");
+
+    let (status, _) = post("/5/manifest", vec![b'#'; limit], "application/toml").await;
+    assert_ne!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -0,0 +1,89 @@
+use tower_http::compression::{
+    predicate::{And, DefaultPredicate, SizeAbove},
+    CompressionLayer, Predicate,
+};
+
+const MIN_COMPRESSED_BYTES: u16 = 1024;
+
+/// gzip or brotli, whichever the client asks for, for responses over 1 KiB.
+pub fn compression() -> CompressionLayer<And<DefaultPredicate, SizeAbove>> {
+    CompressionLayer::new()
+        .compress_when(DefaultPredicate::new().and(SizeAbove::new(MIN_COMPRESSED_BYTES)))
+}
+
+#[cfg(test)]
+async fn gunzip(res: axum::response::Response) -> String {
+    use std::io::Read;
+
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    decoded
+}
+
+#[tokio::test]
+async fn test_compresses_only_large_responses() {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    let router = Router::new()
+        .route("/small", get(|| async { "x".repeat(1000) }))
+        .route("/large", get(|| async { "x".repeat(2000) }))
+        .layer(compression());
+    let get = |uri| {
+        Request::get(uri)
+            .header("Accept-Encoding", "gzip")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let res = router.clone().oneshot(get("/small")).await.unwrap();
+    assert!(res.headers().get("Content-Encoding").is_none());
+
+    let res = router.oneshot(get("/large")).await.unwrap();
+    assert_eq!(res.headers()["Content-Encoding"], "gzip");
+    assert_eq!(gunzip(res).await, "x".repeat(2000));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres instance at DATABASE_URL"]
+async fn test_compresses_quote_list() {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    let db = crate::db::TestDatabase::create().await;
+    sqlx::migrate!().run(&db.pool).await.unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO quotes (id, author, quote)
+        SELECT gen_random_uuid(), 'Santa', repeat('Ho ho ho! ', 50)
+        FROM generate_series(1, 50)
+        "#,
+    )
+    .execute(&db.pool)
+    .await
+    .unwrap();
+
+    let router = Router::new()
+        .route("/19/list", get(crate::day_19::list))
+        .with_state(db.pool.clone())
+        .layer(compression());
+    let req = Request::get("/19/list")
+        .header("Accept-Encoding", "gzip")
+        .body(Body::empty())
+        .unwrap();
+
+    let res = router.oneshot(req).await.unwrap();
+    let encoding = res.headers().get("Content-Encoding").cloned();
+    let body = gunzip(res).await;
+
+    db.remove().await;
+    assert_eq!(encoding.unwrap(), "gzip");
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["quotes"].as_array().unwrap().len(), 3);
+    assert_eq!(body["page"], 1);
+}
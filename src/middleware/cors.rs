@@ -0,0 +1,207 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use super::request_id::X_REQUEST_ID;
+
+const DEFAULT_MAX_AGE: u64 = 3600;
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// `None` allows any origin
+    origins: Option<Vec<HeaderValue>>,
+    max_age: Duration,
+}
+
+impl CorsConfig {
+    /// Reads `CORS_ORIGINS` (comma-separated, default `*`) and `CORS_MAX_AGE` (seconds,
+    /// default 3600)
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("CORS_ORIGINS").ok().as_deref(),
+            std::env::var("CORS_MAX_AGE").ok().as_deref(),
+        )
+    }
+
+    fn new(origins: Option<&str>, max_age: Option<&str>) -> Self {
+        let origins = origins
+            .map(str::trim)
+            .filter(|origins| !origins.is_empty() && *origins != "*")
+            .map(|origins| {
+                origins
+                    .split(',')
+                    .map(str::trim)
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok())
+                    .collect()
+            });
+        let max_age = max_age
+            .and_then(|max_age| max_age.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_AGE);
+
+        Self {
+            origins,
+            max_age: Duration::from_secs(max_age),
+        }
+    }
+
+    pub fn layer(&self) -> CorsLayer {
+        let origin = match &self.origins {
+            Some(origins) => AllowOrigin::list(origins.clone()),
+            None => AllowOrigin::any(),
+        };
+        let headers = [
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            X_REQUEST_ID.clone(),
+        ];
+
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(Any)
+            .allow_headers(headers.clone())
+            .expose_headers(headers)
+            .max_age(self.max_age)
+    }
+
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        match &self.origins {
+            Some(origins) => origins.contains(origin),
+            None => is_origin(origin),
+        }
+    }
+}
+
+/// `scheme://host[:port]` with nothing after it
+fn is_origin(origin: &HeaderValue) -> bool {
+    let Some(uri) = origin.to_str().ok().and_then(|o| o.parse::<Uri>().ok()) else {
+        return false;
+    };
+    uri.scheme().is_some()
+        && uri.authority().is_some()
+        && uri
+            .path_and_query()
+            .map_or(true, |p| p.as_str().is_empty() || p == "/")
+        && !origin.as_bytes().ends_with(b"/")
+}
+
+/// `CorsLayer` answers preflights on its own but just leaves the CORS headers off when the
+/// origin doesn't match, so this has to wrap it to turn those into a `400`
+pub async fn reject_invalid_preflight(
+    State(config): State<Arc<CorsConfig>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_preflight = req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if is_preflight {
+        match req.headers().get(header::ORIGIN) {
+            Some(origin) if config.allows(origin) => {}
+            _ => return (StatusCode::BAD_REQUEST, "Invalid Origin\n").into_response(),
+        }
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+async fn send(config: CorsConfig, req: Request) -> Response {
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    let config = Arc::new(config);
+    let router = Router::new()
+        .route("/", get(|| async { "ok" }))
+        .layer(config.layer())
+        .layer(axum::middleware::from_fn_with_state(
+            config,
+            reject_invalid_preflight,
+        ));
+    router.oneshot(req).await.unwrap()
+}
+
+#[cfg(test)]
+fn preflight(origin: &str) -> Request {
+    Request::options("/")
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .body(axum::body::Body::empty())
+        .unwrap()
+}
+
+#[test]
+fn test_cors_config_from_vars() {
+    let config = CorsConfig::new(None, None);
+    assert!(config.origins.is_none());
+    assert_eq!(config.max_age, Duration::from_secs(3600));
+
+    let config = CorsConfig::new(Some("https://a.com, https://b.com"), Some("60"));
+    assert_eq!(config.origins.unwrap(), ["https://a.com", "https://b.com"]);
+    assert_eq!(config.max_age, Duration::from_secs(60));
+}
+
+#[tokio::test]
+async fn test_cors_preflight() {
+    let res = send(
+        CorsConfig::new(None, Some("120")),
+        preflight("https://a.com"),
+    )
+    .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let headers = res.headers();
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "120");
+    let allowed = headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+        .to_str()
+        .unwrap();
+    assert!(allowed.contains("x-request-id"));
+}
+
+#[tokio::test]
+async fn test_cors_preflight_invalid_origin() {
+    let any = CorsConfig::new(None, None);
+    let res = send(any.clone(), preflight("null")).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = send(any, preflight("https://a.com/path")).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let listed = CorsConfig::new(Some("https://a.com"), None);
+    let res = send(listed.clone(), preflight("https://a.com")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://a.com"
+    );
+    let res = send(listed, preflight("https://evil.com")).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_cors_simple_request() {
+    let req = Request::get("/")
+        .header(header::ORIGIN, "https://a.com")
+        .body(axum::body::Body::empty())
+        .unwrap();
+    let res = send(CorsConfig::new(Some("https://a.com"), None), req).await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let headers = res.headers();
+    assert_eq!(
+        headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://a.com"
+    );
+    let exposed = headers[header::ACCESS_CONTROL_EXPOSE_HEADERS]
+        .to_str()
+        .unwrap();
+    assert!(exposed.contains("content-type"));
+    assert!(exposed.contains("authorization"));
+    assert!(exposed.contains("x-request-id"));
+}
@@ -0,0 +1,61 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use sqlx::types::Uuid;
+use tracing::Span;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Echoes the caller's `X-Request-Id`, or a fresh UUID when there is none, on the response and
+/// records it on the surrounding trace span. Must sit inside the `TraceLayer` for the latter.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = match req.headers().get(&X_REQUEST_ID) {
+        Some(id) => id.clone(),
+        None => {
+            let id = HeaderValue::from_str(&Uuid::new_v4().to_string()).unwrap();
+            req.headers_mut().insert(X_REQUEST_ID.clone(), id.clone());
+            id
+        }
+    };
+    Span::current().record("request_id", id.to_str().unwrap_or_default());
+
+    let mut res = next.run(req).await;
+    res.headers_mut().insert(X_REQUEST_ID.clone(), id);
+    res
+}
+
+#[cfg(test)]
+async fn send(id: Option<&'static str>) -> Response {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    let router = Router::new()
+        .route("/", get(|| async { "ok" }))
+        .layer(axum::middleware::from_fn(request_id));
+    let mut req = Request::get("/");
+    if let Some(id) = id {
+        req = req.header(&X_REQUEST_ID, id);
+    }
+    router
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_request_id_echoed() {
+    let res = send(Some("my-request")).await;
+
+    assert_eq!(res.headers()[&X_REQUEST_ID], "my-request");
+}
+
+#[tokio::test]
+async fn test_request_id_generated() {
+    let res = send(None).await;
+
+    let id = res.headers()[&X_REQUEST_ID].to_str().unwrap();
+    assert!(Uuid::parse_str(id).is_ok());
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use axum::{http::StatusCode, BoxError};
+
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Turns errors from the `tower` timeout layer into responses.
+pub async fn handle_timeout(err: BoxError) -> (StatusCode, &'static str) {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        (StatusCode::GATEWAY_TIMEOUT, "Request timed out\n")
+    } else {
+        tracing::error!(error = %err, "unhandled middleware error");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error\n")
+    }
+}
+
+#[tokio::test]
+async fn test_slow_handler_times_out() {
+    use axum::{body::Body, error_handling::HandleErrorLayer, http::Request, routing::get, Router};
+    use tower::{ServiceBuilder, ServiceExt};
+
+    tokio::time::pause();
+    let router = Router::new()
+        .route("/fast", get(|| async { "ok" }))
+        .route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(REQUEST_TIMEOUT * 2).await;
+                "too late"
+            }),
+        )
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_timeout))
+                .timeout(REQUEST_TIMEOUT),
+        );
+    let get = |uri| Request::get(uri).body(Body::empty()).unwrap();
+
+    let res = router.clone().oneshot(get("/fast")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let start = tokio::time::Instant::now();
+    let res = router.oneshot(get("/slow")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert!((REQUEST_TIMEOUT..REQUEST_TIMEOUT * 2).contains(&start.elapsed()));
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"Request timed out\n");
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{header::USER_AGENT, Request, Response},
+};
+use tracing::{field::Empty, Span};
+
+use super::request_id::X_REQUEST_ID;
+
+const MAX_USER_AGENT_CHARS: usize = 100;
+
+/// Span for `TraceLayer`. A missing `request_id` is filled in by the request id middleware.
+pub fn make_span(req: &Request<Body>) -> Span {
+    let span = tracing::info_span!(
+        "",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id = Empty,
+        user_agent = Empty,
+        matched_path = Empty,
+    );
+
+    let header = |name| req.headers().get(name).and_then(|x| x.to_str().ok());
+    if let Some(request_id) = header(&X_REQUEST_ID) {
+        span.record("request_id", request_id);
+    }
+    if let Some(user_agent) = header(&USER_AGENT) {
+        let user_agent: String = user_agent.chars().take(MAX_USER_AGENT_CHARS).collect();
+        span.record("user_agent", user_agent);
+    }
+    if let Some(path) = req.extensions().get::<MatchedPath>() {
+        span.record("matched_path", path.as_str());
+    }
+
+    span
+}
+
+pub fn on_response<B>(res: &Response<B>, latency: Duration, _span: &Span) {
+    let status = res.status().as_u16();
+    let request_id = res
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+    if res.status().is_server_error() {
+        tracing::error!(status, request_id, latency = ?latency);
+    } else if res.status().is_client_error() {
+        tracing::warn!(status, request_id, latency = ?latency);
+    } else {
+        tracing::info!(status, request_id, latency = ?latency);
+    }
+}
+
+/// Collects every field recorded on any span, last write wins.
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SpanFields(std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, String>>>);
+
+#[cfg(test)]
+impl tracing::field::Visit for SpanFields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        let mut fields = self.0.lock().unwrap();
+        fields.insert(field.name().to_owned(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        let mut fields = self.0.lock().unwrap();
+        fields.insert(field.name().to_owned(), format!("{value:?}"));
+    }
+}
+
+#[cfg(test)]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanFields {
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        _id: &tracing::span::Id,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        attrs.record(&mut self.clone());
+    }
+
+    fn on_record(
+        &self,
+        _id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        values.record(&mut self.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_span_fields() {
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+    use tower_http::trace::TraceLayer;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let fields = SpanFields::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+    let router = Router::new()
+        .route("/12/place/:team/:column", get(|| async { "ok" }))
+        .layer(TraceLayer::new_for_http().make_span_with(make_span));
+    let req = Request::get("/12/place/cookie/1")
+        .header(&X_REQUEST_ID, "abc-123")
+        .header(USER_AGENT, "x".repeat(150))
+        .body(Body::empty())
+        .unwrap();
+    router.oneshot(req).await.unwrap();
+
+    let fields = fields.0.lock().unwrap();
+    assert_eq!(fields["method"], "GET");
+    assert_eq!(fields["request_id"], "abc-123");
+    assert_eq!(fields["user_agent"], "x".repeat(100));
+    assert_eq!(fields["matched_path"], "/12/place/:team/:column");
+}
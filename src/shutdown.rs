@@ -0,0 +1,163 @@
+use std::{
+    future::{Future, IntoFuture},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+    Router,
+};
+use shuttle_runtime::{CustomError, Error};
+use tokio::{net::TcpListener, sync::watch};
+
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Stand-in for `shuttle_axum::AxumService` that stops accepting connections on SIGTERM or
+/// Ctrl-C and gives in-flight requests up to [`DRAIN_TIMEOUT`] to finish.
+pub struct GracefulAxum(pub Router);
+
+#[shuttle_runtime::async_trait]
+impl shuttle_runtime::Service for GracefulAxum {
+    async fn bind(self, addr: SocketAddr) -> Result<(), Error> {
+        let listener = TcpListener::bind(addr).await.map_err(CustomError::new)?;
+        serve(listener, self.0, shutdown_signal(), DRAIN_TIMEOUT)
+            .await
+            .map_err(CustomError::new)?;
+        Ok(())
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+    };
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+async fn count_in_flight(
+    State(in_flight): State<Arc<AtomicUsize>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    in_flight.fetch_add(1, Ordering::Relaxed);
+    let res = next.run(req).await;
+    in_flight.fetch_sub(1, Ordering::Relaxed);
+    res
+}
+
+async fn serve(
+    listener: TcpListener,
+    router: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    drain_timeout: Duration,
+) -> std::io::Result<()> {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let router = router.layer(axum::middleware::from_fn_with_state(
+        in_flight.clone(),
+        count_in_flight,
+    ));
+
+    let (signalled, mut on_signal) = watch::channel(0);
+    let shutdown = {
+        let in_flight = in_flight.clone();
+        async move {
+            shutdown.await;
+            let pending = in_flight.load(Ordering::Relaxed);
+            tracing::info!(pending, "shutting down, draining in-flight requests");
+            let _ = signalled.send(pending);
+        }
+    };
+    let server = axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown)
+        .into_future();
+    let deadline = async {
+        let _ = on_signal.changed().await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    tokio::select! {
+        res = server => res?,
+        _ = deadline => {
+            tracing::warn!(
+                abandoned = in_flight.load(Ordering::Relaxed),
+                "drain timed out, abandoning requests"
+            );
+            return Ok(());
+        }
+    }
+    let pending = *on_signal.borrow();
+    let drained = pending - in_flight.load(Ordering::Relaxed).min(pending);
+    tracing::info!(drained, "drained in-flight requests");
+    Ok(())
+}
+
+#[cfg(test)]
+async fn slow_get(addr: SocketAddr) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).await.unwrap();
+    res
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_drains_slow_request() {
+    use axum::routing::get;
+
+    let router = Router::new().route(
+        "/slow",
+        get(|| async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            "done"
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(serve(
+        listener,
+        router,
+        async {
+            let _ = stopped.await;
+        },
+        Duration::from_secs(5),
+    ));
+
+    let request = tokio::spawn(slow_get(addr));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    stop.send(()).unwrap();
+
+    let res = request.await.unwrap();
+    assert!(res.starts_with("HTTP/1.1 200 OK"));
+    assert!(res.ends_with("done"));
+    tokio::time::timeout(Duration::from_secs(1), server)
+        .await
+        .expect("server should exit once drained")
+        .unwrap()
+        .unwrap();
+    assert!(TcpListener::bind(addr).await.is_ok());
+}